@@ -1,8 +1,14 @@
 use anyhow::Result;
-use clap::{command, Args};
+use clap::{Args, ValueEnum};
 use log::info;
 
-use crate::{commands::args::PassArgs, core::{encryption, file_system, keyring}};
+use crate::{commands::{args::{MetadataArgs, OtpParamArgs, PassArgs}, prompt}, core::{clock::{self, Clock, SystemClock}, config, entry::{Entry, OtpKind}, keyring, oathtool, secret::{self, SecretFormat}, store}};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtpType {
+    Totp,
+    Hotp,
+}
 
 #[derive(Args, Debug)]
 #[command(about = "Add a new OTP entry for <name> encrypting <secret>")]
@@ -12,19 +18,60 @@ pub struct AddEntry {
 
     #[arg(short, long, help = "Secret for the OTP entry")]
     secret: String,
+
+    #[arg(long, value_enum, default_value = "base32", help = "Encoding of <secret>")]
+    secret_format: SecretFormat,
+
+    #[arg(short, long, help = "Save without asking to confirm the first code")]
+    yes: bool,
+
+    #[arg(short = 't', long = "type", value_enum, default_value = "totp", help = "Type of the OTP entry")]
+    otp_type: OtpType,
+
+    #[arg(long, default_value_t = 0, help = "Initial counter for HOTP entries")]
+    counter: u64,
+
+    #[command(flatten)]
+    params: OtpParamArgs,
+
+    #[command(flatten)]
+    metadata: MetadataArgs,
 }
 
 impl AddEntry {
     pub fn run(&self) -> Result<()> {
         let password = keyring::handle_password(&self.args)?;
 
-        let app_dir = file_system::get_app_data_dir().unwrap();
-        let secret_path = app_dir + format!("secrets/{}.bin", self.args.service_name).as_str();
-        let encrypted = encryption::encrypt(&self.secret, &password);
-        let encrypted_bin = bincode::serialize(&encrypted.unwrap()).unwrap();
+        let secret = secret::normalize(&self.secret, self.secret_format)?;
+
+        let mut entry = match self.otp_type {
+            OtpType::Totp => Entry::totp(&secret),
+            OtpType::Hotp => Entry::hotp(&secret, self.counter),
+        };
+        self.params.apply(&mut entry);
+        self.metadata.apply(&mut entry);
+        entry.created_at = Some(SystemClock.now()?);
+
+        // Show a code before saving so it can be checked against the website
+        show_first_code(&self.args.service_name, &entry)?;
+        if !self.yes && !prompt::confirm("Does it match? Save the entry?", true)? {
+            println!("Entry not saved.");
+            return Ok(());
+        }
 
-        file_system::write_bin(&secret_path, &encrypted_bin)?;
+        store::save(&self.args.service_name, &entry, &password)?;
         info!("Added entry: {}", self.args.service_name);
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+pub fn show_first_code(service_name: &str, entry: &Entry) -> Result<()> {
+    let config = config::read();
+    let params = entry.params(&config);
+    let first_code = match entry.kind {
+        OtpKind::Totp => oathtool::generate(&entry.secret, &params, &clock::configured(&config))?,
+        OtpKind::Hotp { counter } => oathtool::generate_hotp(&entry.secret, counter, &params)?,
+    };
+    println!("First OTP for {}: {}", service_name, first_code);
+    Ok(())
+}
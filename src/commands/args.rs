@@ -1,4 +1,6 @@
-use clap::{arg, Args};
+use clap::Args;
+
+use crate::core::{entry::Entry, oathtool::Algorithm};
 
 #[derive(Debug, Args)]
 pub struct PassArgs {
@@ -22,6 +24,114 @@ impl Default for PassArgs {
     }
 }
 
+/// Password options for commands that work on the whole vault.
+#[derive(Debug, Args)]
+pub struct VaultArgs {
+    #[arg(short, long, global = true, help = "Password for managing secrets encryption")]
+    pub password: Option<String>,
+
+    #[arg(long, global = true, default_value = "false", help = "Use stored password from keyring")]
+    pub use_stored: Option<bool>,
+}
+
+/// Password option for commands that reveal secrets, which never take the
+/// stored password, see `keyring::reenter_password`.
+#[derive(Debug, Args)]
+pub struct RevealArgs {
+    #[arg(short, long, global = true, help = "Password for managing secrets encryption [default: asked for]")]
+    pub password: Option<String>,
+}
+
+/// Where `keyring::handle_password` gets the password from.
+pub trait PasswordOptions {
+    fn password(&self) -> Option<&str>;
+    fn use_stored(&self) -> bool;
+}
+
+impl PasswordOptions for PassArgs {
+    fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    fn use_stored(&self) -> bool {
+        self.use_stored.unwrap_or(false)
+    }
+}
+
+impl PasswordOptions for VaultArgs {
+    fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    fn use_stored(&self) -> bool {
+        self.use_stored.unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct OtpParamArgs {
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=10), help = "Number of digits of the generated codes [default: from config]")]
+    pub digits: Option<u32>,
+
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), help = "Time step in seconds for TOTP entries [default: from config]")]
+    pub period: Option<u64>,
+
+    #[arg(long, value_enum, ignore_case = true, help = "HMAC algorithm [default: SHA1]")]
+    pub algorithm: Option<Algorithm>,
+
+    #[arg(long, help = "Unix time from which TOTP time steps are counted [default: 0]")]
+    pub t0: Option<u64>,
+}
+
+impl OtpParamArgs {
+    pub fn apply(&self, entry: &mut Entry) {
+        entry.digits = self.digits;
+        entry.period = self.period;
+        entry.algorithm = self.algorithm;
+        entry.t0 = self.t0;
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct MetadataArgs {
+    #[arg(long, help = "Issuer of the account, e.g. the company name")]
+    pub issuer: Option<String>,
+
+    #[arg(long, help = "Account or username at the issuer")]
+    pub account: Option<String>,
+
+    #[arg(long = "tag", help = "Tag to add to the entry (repeatable)")]
+    pub tags: Vec<String>,
+
+    #[arg(long, help = "Free-form notes")]
+    pub notes: Option<String>,
+
+    #[arg(long, help = "Login URL of the service")]
+    pub url: Option<String>,
+}
+
+impl MetadataArgs {
+    /// Sets the given fields and adds the tags. An empty value clears a field.
+    pub fn apply(&self, entry: &mut Entry) {
+        let fields = [
+            (&self.issuer, &mut entry.issuer),
+            (&self.account, &mut entry.account),
+            (&self.notes, &mut entry.notes),
+            (&self.url, &mut entry.url),
+        ];
+        for (value, field) in fields {
+            if let Some(value) = value {
+                *field = Some(value.clone()).filter(|value| !value.is_empty());
+            }
+        }
+        for tag in &self.tags {
+            if !entry.has_tag(tag) {
+                entry.tags.push(tag.clone());
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -87,4 +197,62 @@ mod tests {
         assert!(debug_str.contains("password"));
         assert!(debug_str.contains("use_stored"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_password_options() {
+        let pass_args = PassArgs {
+            service_name: "gmail".to_string(),
+            password: Some("secret123".to_string()),
+            use_stored: None,
+        };
+        let vault_args = VaultArgs {
+            password: None,
+            use_stored: Some(true),
+        };
+
+        assert_eq!(pass_args.password(), Some("secret123"));
+        assert!(!pass_args.use_stored());
+        assert_eq!(vault_args.password(), None);
+        assert!(vault_args.use_stored());
+    }
+
+    #[test]
+    fn test_otp_param_args_apply() {
+        let args = OtpParamArgs {
+            digits: Some(8),
+            period: Some(60),
+            algorithm: Some(Algorithm::Sha256),
+            t0: None,
+        };
+        let mut entry = Entry::totp("JBSWY3DPEHPK3PXP");
+        args.apply(&mut entry);
+
+        assert_eq!(entry.digits, Some(8));
+        assert_eq!(entry.period, Some(60));
+        assert_eq!(entry.algorithm, Some(Algorithm::Sha256));
+        assert_eq!(entry.t0, None);
+    }
+
+    #[test]
+    fn test_metadata_args_apply() {
+        let mut entry = Entry::totp("JBSWY3DPEHPK3PXP");
+        entry.notes = Some("old notes".to_string());
+        entry.url = Some("https://example.com".to_string());
+        entry.tags = vec!["work".to_string()];
+
+        let args = MetadataArgs {
+            issuer: Some("GitHub".to_string()),
+            account: None,
+            tags: vec!["Work".to_string(), "dev".to_string()],
+            notes: Some(String::new()),
+            url: None,
+        };
+        args.apply(&mut entry);
+
+        assert_eq!(entry.issuer.as_deref(), Some("GitHub"));
+        assert_eq!(entry.account, None);
+        assert_eq!(entry.notes, None);
+        assert_eq!(entry.url.as_deref(), Some("https://example.com"));
+        assert_eq!(entry.tags, ["work", "dev"]);
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clap::{command, Args, Subcommand};
+use clap::{Args, Subcommand};
 use log::warn;
 use crate::core::{config};
 
@@ -43,6 +43,15 @@ impl SetConfig {
             "duration" => cfg.duration = self.value.parse::<u32>().unwrap(),
             "digits" => cfg.digits = self.value.parse::<u32>().unwrap(),
             "wait_for_next_time" => cfg.wait_for_next_time = self.value.parse::<u32>().unwrap(),
+            "wait_mode" => cfg.wait_mode = self.value.parse()?,
+            "time_offset" => cfg.time_offset = self.value.parse::<i64>()?,
+            "time_server" => cfg.time_server = self.value.clone(),
+            "time_url" => cfg.time_url = self.value.clone(),
+            "kdf_memory_kib" => cfg.kdf_memory_kib = self.value.parse::<u32>()?,
+            "kdf_iterations" => cfg.kdf_iterations = self.value.parse::<u32>()?,
+            "kdf_parallelism" => cfg.kdf_parallelism = self.value.parse::<u32>()?,
+            "storage" => cfg.storage = self.value.parse()?,
+            "secret_history_days" => cfg.secret_history_days = self.value.parse::<u32>()?,
             _ => {
                 println!("Unknown configuration key: {}", self.key);
                 return Ok(());
@@ -0,0 +1,31 @@
+use anyhow::Result;
+use clap::Args;
+use log::info;
+
+use crate::{commands::args::{MetadataArgs, PassArgs}, core::{keyring, store}};
+
+#[derive(Args, Debug)]
+#[command(about = "Edit the metadata of an existing OTP entry, an empty value clears a field")]
+pub struct EditEntry {
+    #[command(flatten)]
+    pub args: PassArgs,
+
+    #[command(flatten)]
+    metadata: MetadataArgs,
+
+    #[arg(long = "remove-tag", help = "Tag to remove from the entry (repeatable)")]
+    remove_tags: Vec<String>,
+}
+
+impl EditEntry {
+    pub fn run(&self) -> Result<()> {
+        let password = keyring::handle_password(&self.args)?;
+        store::update(&self.args.service_name, &password, |entry| {
+            entry.tags.retain(|tag| !self.remove_tags.iter().any(|removed| removed.eq_ignore_ascii_case(tag)));
+            self.metadata.apply(entry);
+            Ok(())
+        })?;
+        info!("Updated entry: {}", self.args.service_name);
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::{Error, Result};
+use clap::{Args, ValueEnum};
+use log::info;
+use std::path::PathBuf;
+
+use crate::{commands::{args::RevealArgs, prompt}, core::{clock::{Clock, SystemClock}, config, entry::Entry, file_system, keyring, migration, names, qr, store, uri}};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One otpauth:// URI per line, as read by import-uri
+    Uri,
+    /// QR codes for the "Transfer accounts" import of Google Authenticator
+    GaMigration,
+}
+
+#[derive(Args, Debug)]
+#[command(about = "Export all entries, which needs the password even when one is stored")]
+pub struct Export {
+    #[arg(help = "Only export the entries in this group, e.g. work/")]
+    group: Option<String>,
+
+    #[arg(short, long, value_enum, default_value = "uri", help = "Export format")]
+    format: ExportFormat,
+
+    #[arg(short, long, help = "Directory to write the ga-migration QR codes to as PNG files, instead of drawing them in the terminal")]
+    out: Option<PathBuf>,
+
+    #[arg(long, help = "Swap dark and light modules, for terminals with a dark background")]
+    invert: bool,
+
+    #[command(flatten)]
+    pub args: RevealArgs,
+}
+
+impl Export {
+    pub fn run(&self) -> Result<()> {
+        if self.out.is_some() && self.format != ExportFormat::GaMigration {
+            return Err(Error::msg("--out is only supported with --format ga-migration"));
+        }
+
+        // The export reveals every secret, so a stored password is not enough
+        let password = keyring::reenter_password(self.args.password.as_deref())?;
+        let group = self.group.as_deref().unwrap_or_default();
+        let entries: Vec<(String, Entry)> = store::load_all(&password)?
+            .into_iter()
+            .filter(|(name, _)| names::in_group(name, group))
+            .collect();
+        if entries.is_empty() {
+            return Err(Error::msg("No entries to export"));
+        }
+
+        match self.format {
+            ExportFormat::Uri => {
+                let config = config::read();
+                for (name, entry) in &entries {
+                    println!("{}", uri::from_entry(name, entry, &entry.params(&config)));
+                }
+                Ok(())
+            }
+            ExportFormat::GaMigration => self.export_ga_migration(&entries),
+        }
+    }
+
+    fn export_ga_migration(&self, entries: &[(String, Entry)]) -> Result<()> {
+        // The app stores the batch id as a 32-bit integer
+        let batch_id = SystemClock.now()? & 0x7fff_ffff;
+        let (uris, skipped) = migration::export(entries, &config::read(), batch_id);
+
+        for (i, uri) in uris.iter().enumerate() {
+            let part = format!("{}-of-{}", i + 1, uris.len());
+            match &self.out {
+                Some(dir) => {
+                    file_system::create_dir_all(dir)?;
+                    let path = dir.join(format!("ga-migration-{}.png", part));
+                    file_system::write_bin(&path.to_string_lossy(), &qr::to_png(uri)?)?;
+                    info!("Wrote {}", path.display());
+                }
+                None => {
+                    println!("QR code {} of {}:", i + 1, uris.len());
+                    println!("{}", qr::to_terminal(uri, self.invert)?);
+                    if i + 1 < uris.len() {
+                        prompt::pause("Scan it, then press Enter for the next code.")?;
+                    }
+                }
+            }
+        }
+
+        let exported = entries.len() - skipped.len();
+        println!("Exported {} of {} entries in {} QR codes.", exported, entries.len(), uris.len());
+        for skipped in &skipped {
+            println!("Skipped {}: {}", skipped.name, skipped.reason);
+        }
+        if uris.is_empty() {
+            return Err(Error::msg("None of the entries can be imported by Google Authenticator"));
+        }
+        Ok(())
+    }
+}
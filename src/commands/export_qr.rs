@@ -0,0 +1,46 @@
+use anyhow::{Error, Result};
+use clap::Args;
+use log::info;
+use std::path::PathBuf;
+
+use crate::{commands::args::RevealArgs, core::{config, file_system, keyring, qr, store, uri}};
+
+#[derive(Args, Debug)]
+#[command(about = "Show the otpauth:// URI of an entry as a QR code, which needs the password even when one is stored")]
+pub struct ExportQr {
+    #[arg(name = "name", short = 'n', long, help = "Name of the service")]
+    service_name: String,
+
+    #[arg(short, long, help = "Write a PNG or SVG file, chosen by extension, instead of drawing in the terminal")]
+    out: Option<PathBuf>,
+
+    #[arg(long, help = "Swap dark and light modules, for terminals with a dark background")]
+    invert: bool,
+
+    #[command(flatten)]
+    pub args: RevealArgs,
+}
+
+impl ExportQr {
+    pub fn run(&self) -> Result<()> {
+        // The code reveals the secret, so a stored password is not enough
+        let password = keyring::reenter_password(self.args.password.as_deref())?;
+        let entry = store::load(&self.service_name, &password)?;
+        let params = entry.params(&config::read());
+        let uri = uri::from_entry(&self.service_name, &entry, &params);
+
+        let Some(out) = &self.out else {
+            println!("{}", qr::to_terminal(&uri, self.invert)?);
+            return Ok(());
+        };
+        let extension = out.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        let contents = match extension.to_ascii_lowercase().as_str() {
+            "png" => qr::to_png(&uri)?,
+            "svg" => qr::to_svg(&uri)?.into_bytes(),
+            _ => return Err(Error::msg(format!("Unsupported output format: {} (use .png or .svg)", out.display()))),
+        };
+        file_system::write_bin(&out.to_string_lossy(), &contents)?;
+        info!("Wrote QR code for {} to {}", self.service_name, out.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::{commands::args::RevealArgs, core::{config, keyring, store, uri}};
+
+#[derive(Args, Debug)]
+#[command(about = "Print the otpauth:// URI of an entry, which needs the password even when one is stored")]
+pub struct ExportUri {
+    #[arg(name = "name", short = 'n', long, help = "Name of the service")]
+    service_name: String,
+
+    #[command(flatten)]
+    pub args: RevealArgs,
+}
+
+impl ExportUri {
+    pub fn run(&self) -> Result<()> {
+        // The URI reveals the secret, so a stored password is not enough
+        let password = keyring::reenter_password(self.args.password.as_deref())?;
+        let entry = store::load(&self.service_name, &password)?;
+        let params = entry.params(&config::read());
+
+        println!("{}", uri::from_entry(&self.service_name, &entry, &params));
+        Ok(())
+    }
+}
@@ -1,30 +1,128 @@
 use anyhow::Result;
-use clap::{command, Args};
+use clap::Args;
+use std::{io::{self, IsTerminal, Write}, thread, time::Duration};
 
-use crate::{commands::args::PassArgs, core::{encryption, file_system, keyring, oathtool}};
+use crate::{commands::{args::PassArgs, prompt}, core::{clock::{self, Clock, FixedClock, SystemClock}, config::{self, AppConfig, WaitMode}, entry::{Entry, OtpKind}, keyring, oathtool, store}};
+
+/// Largest `--window`, already more than a day of 30 second steps.
+const MAX_WINDOW: u64 = 1000;
 
 #[derive(Args, Debug)]
 #[command(about = "Get the OTP for a specific entry")]
 pub struct GetSecret {
     #[command(flatten)]
     pub args: PassArgs,
+
+    #[arg(long, value_parser = clock::parse_timestamp, help = "Show the TOTP at this time (RFC 3339 or Unix seconds)")]
+    at: Option<u64>,
+
+    #[arg(long, value_parser = clap::value_parser!(u64).range(..=MAX_WINDOW), help = "Show the TOTPs for N time steps before and after the current one (at most 1000)")]
+    window: Option<u64>,
 }
 
 impl GetSecret {
     pub fn run(&self) -> Result<()> {
         let password = keyring::handle_password(&self.args)?;
-        let app_dir = file_system::get_app_data_dir()?;
-        let secret_path = app_dir + format!("{}.bin", self.args.service_name).as_str();
+        let service_name = prompt::resolve_service_name(&self.args.service_name, &password)?;
+        let entry = store::load(&service_name, &password)?;
+        let config = config::read();
 
-        let secret_bin = file_system::read_bin(&secret_path)?;
-        let encrypted = bincode::deserialize::<encryption::EncryptedSecret>(&secret_bin)?;
-        let secret = encryption::decrypt(&encrypted, &password).unwrap();
+        if self.at.is_some() || self.window.is_some() {
+            let clock: Box<dyn Clock> = match self.at {
+                Some(timestamp) => Box::new(FixedClock(timestamp)),
+                None => Box::new(clock::configured(&config)),
+            };
+            return self.print_codes(&service_name, &entry, &config, clock.as_ref());
+        }
 
-        let otp = oathtool::generate(&secret, 6, 30);
+        let used_at = SystemClock.now()?;
+        let (otp, valid_from) = match entry.kind {
+            // Reading a TOTP code writes nothing, only HOTP entries record their last use
+            OtpKind::Totp => self.totp(&service_name, &entry, &config, &clock::configured(&config))?,
+            // The counter is persisted before the code is shown, so a code is never reused
+            OtpKind::Hotp { .. } => store::update(&service_name, &password, |entry| {
+                let params = entry.params(&config);
+                let OtpKind::Hotp { counter } = &mut entry.kind else {
+                    return Err(anyhow::anyhow!("Entry is no longer an HOTP entry"));
+                };
+                let otp = oathtool::generate_hotp(&entry.secret, *counter, &params)?;
+                *counter += 1;
+                entry.last_used_at = Some(used_at);
+                Ok((otp, None))
+            })?,
+        };
         arboard::Clipboard::new()?.set_text(otp.clone())?;
 
-        println!("OTP for {}: {}", self.args.service_name, otp);
+        println!("OTP for {}: {}", service_name, otp);
         println!("OTP copied to clipboard.");
+        if let Some(valid_from) = valid_from {
+            countdown(&clock::configured(&config), valid_from)?;
+        }
+        Ok(())
+    }
+
+    /// Current TOTP code, or the next one when the current time step is
+    /// about to end (see `wait_for_next_time` and `wait_mode`). The time the
+    /// code becomes valid is returned when that is still to come.
+    fn totp(&self, service_name: &str, entry: &Entry, config: &AppConfig, clock: &dyn Clock) -> Result<(String, Option<u64>)> {
+        let params = entry.params(config);
+        let now = clock.now()?;
+        let remaining = oathtool::seconds_remaining(&params, now);
+        if remaining > config.wait_for_next_time as u64 {
+            return Ok((oathtool::generate_at(&entry.secret, &params, now)?, None));
+        }
+
+        match config.wait_mode {
+            WaitMode::Wait => {
+                println!("Current OTP expires in {}s, waiting for the next one...", remaining);
+                thread::sleep(Duration::from_secs(remaining));
+                Ok((oathtool::generate(&entry.secret, &params, clock)?, None))
+            }
+            WaitMode::Both => {
+                let current = oathtool::generate_at(&entry.secret, &params, now)?;
+                println!("Current OTP for {}: {} (expires in {}s)", service_name, current, remaining);
+                let next = oathtool::generate_at(&entry.secret, &params, now + remaining)?;
+                Ok((next, Some(now + remaining)))
+            }
+        }
+    }
+
+    /// Prints codes around a point in time for debugging, without touching
+    /// the clipboard.
+    fn print_codes(&self, service_name: &str, entry: &Entry, config: &AppConfig, clock: &dyn Clock) -> Result<()> {
+        if entry.kind != OtpKind::Totp {
+            return Err(anyhow::anyhow!("--at and --window only apply to TOTP entries"));
+        }
+        let params = entry.params(config);
+        let timestamp = clock.now()?;
+
+        println!("OTPs for {} at {}:", service_name, clock::format_timestamp(timestamp));
+        for (offset, start, code) in oathtool::generate_window(&entry.secret, &params, timestamp, self.window.unwrap_or(0))? {
+            println!("{:>+4}  {}  {}", offset, clock::format_timestamp(start), code);
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Counts the seconds until `valid_from` down in place, so it is clear when
+/// the current code expires and the next one takes over. Without a
+/// terminal a single line is printed instead.
+fn countdown(clock: &dyn Clock, valid_from: u64) -> Result<()> {
+    let mut stdout = io::stdout();
+    if !stdout.is_terminal() {
+        println!("Next OTP is valid in {}s.", valid_from.saturating_sub(clock.now()?));
+        return Ok(());
+    }
+
+    loop {
+        let remaining = valid_from.saturating_sub(clock.now()?);
+        if remaining == 0 {
+            break;
+        }
+        print!("\rCurrent OTP expires in {}s, then the next one is valid. ", remaining);
+        stdout.flush()?;
+        thread::sleep(Duration::from_secs(1));
+    }
+    println!("\rCurrent OTP expired, the next one is valid now.{}", " ".repeat(12));
+    Ok(())
+}
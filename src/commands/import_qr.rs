@@ -0,0 +1,93 @@
+use anyhow::Result;
+use clap::Args;
+use log::{error, info};
+use std::path::PathBuf;
+
+use crate::{commands::{add, args::VaultArgs, import_uri::{self, Imports}, prompt}, core::{entry::Entry, keyring, qr, store, uri}};
+
+#[derive(Args, Debug)]
+#[command(about = "Import entries from QR codes in PNG or JPEG images")]
+pub struct ImportQr {
+    #[arg(required = true, help = "Image files with otpauth:// or otpauth-migration:// QR codes")]
+    files: Vec<PathBuf>,
+
+    #[arg(short = 'n', long = "name", help = "Name of the service when the images hold a single entry [default: issuer/account]")]
+    service_name: Option<String>,
+
+    #[arg(short, long, help = "Save without asking to confirm each entry")]
+    yes: bool,
+
+    #[command(flatten)]
+    pub args: VaultArgs,
+}
+
+impl ImportQr {
+    pub fn run(&self) -> Result<()> {
+        let mut imports = Imports::default();
+        let mut failed = 0;
+        for path in &self.files {
+            match qr::read_image(path) {
+                Ok(contents) => for content in contents {
+                    if let Err(e) = imports.add(&content) {
+                        error!("{}: {}", path.display(), e);
+                        failed += 1;
+                    }
+                },
+                Err(e) => {
+                    error!("{}", e);
+                    failed += 1;
+                }
+            }
+        }
+        if self.service_name.is_some() && imports.entries.len() > 1 {
+            return Err(anyhow::anyhow!("--name needs a single entry, found {}", imports.entries.len()));
+        }
+
+        let password = if imports.entries.is_empty() { String::new() } else { keyring::handle_password(&self.args)? };
+        let mut imported = 0;
+        let mut skipped = imports.skipped();
+        for entry in imports.entries.drain(..) {
+            match self.import(entry, &password) {
+                Ok(Some(name)) => {
+                    info!("Imported {}", name);
+                    imported += 1;
+                }
+                Ok(None) => skipped += 1,
+                Err(e) => {
+                    error!("{}", e);
+                    failed += 1;
+                }
+            }
+        }
+
+        println!("Imported {} of {} entries.", imported, imported + skipped + failed);
+        imports.report();
+        if failed > 0 {
+            return Err(anyhow::anyhow!("{} entries could not be imported", failed));
+        }
+        Ok(())
+    }
+
+    /// Previews the entry and saves it once confirmed. Returns the name it
+    /// was saved under, or `None` when the user declined.
+    fn import(&self, entry: Entry, password: &str) -> Result<Option<String>> {
+        let name = match &self.service_name {
+            Some(name) => name.clone(),
+            None => uri::suggest_name(&entry)?,
+        };
+        if store::exists(&name, password)? {
+            return Err(anyhow::anyhow!("Entry already exists: {}", name));
+        }
+
+        // Show a code before saving so it can be checked against the website
+        add::show_first_code(&name, &entry)?;
+        if !self.yes && !prompt::confirm(&format!("Save the entry {}?", name), true)? {
+            println!("Entry {} not saved.", name);
+            return Ok(None);
+        }
+
+        import_uri::save_new(&name, entry, password)?;
+        Ok(Some(name))
+    }
+}
+
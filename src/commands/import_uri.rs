@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use log::{error, info};
+use std::io::{self, Read};
+
+use crate::{commands::args::VaultArgs, core::{clock::{Clock, SystemClock}, entry::Entry, keyring, migration, store, uri}};
+
+#[derive(Args, Debug)]
+#[command(about = "Import entries from otpauth:// and otpauth-migration:// URIs")]
+pub struct ImportUri {
+    #[arg(help = "URI to import; without it URIs are read one per line from --file or stdin")]
+    uri: Option<String>,
+
+    #[arg(short, long, conflicts_with = "uri", help = "File with one URI per line, - for stdin")]
+    file: Option<String>,
+
+    #[arg(short = 'n', long = "name", requires = "uri", help = "Name of the service [default: issuer/account from the URI]")]
+    service_name: Option<String>,
+
+    #[command(flatten)]
+    pub args: VaultArgs,
+}
+
+impl ImportUri {
+    pub fn run(&self) -> Result<()> {
+        let password = keyring::handle_password(&self.args)?;
+
+        let input = match (&self.uri, self.file.as_deref()) {
+            (Some(uri), _) => uri.clone(),
+            (None, Some(path)) if path != "-" => std::fs::read_to_string(path).with_context(
+                || format!("Failed to read file: {}", path)
+            )?,
+            (None, _) => {
+                let mut input = String::new();
+                io::stdin().read_to_string(&mut input)?;
+                input
+            }
+        };
+
+        let mut imports = Imports::default();
+        let mut failed = 0;
+        let lines = input.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        for (line_number, line) in lines {
+            if let Err(e) = imports.add(line) {
+                error!("Line {}: {}", line_number, e);
+                failed += 1;
+            }
+        }
+        if self.service_name.is_some() && imports.entries.len() > 1 {
+            return Err(anyhow::anyhow!("--name needs a single entry, found {}", imports.entries.len()));
+        }
+
+        let mut imported = 0;
+        for entry in imports.entries.drain(..) {
+            match self.import(entry, &password) {
+                Ok(name) => {
+                    info!("Imported {}", name);
+                    imported += 1;
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    failed += 1;
+                }
+            }
+        }
+
+        println!("Imported {} of {} entries.", imported, imported + failed + imports.skipped());
+        imports.report();
+        if failed > 0 {
+            return Err(anyhow::anyhow!("{} entries could not be imported", failed));
+        }
+        Ok(())
+    }
+
+    fn import(&self, entry: Entry, password: &str) -> Result<String> {
+        let name = match &self.service_name {
+            Some(name) => name.clone(),
+            None => uri::suggest_name(&entry)?,
+        };
+        save_new(&name, entry, password)?;
+        Ok(name)
+    }
+}
+
+/// Entries read from `otpauth://` and `otpauth-migration://` URIs, along
+/// with what could not be imported from migration payloads.
+#[derive(Default)]
+pub struct Imports {
+    pub entries: Vec<Entry>,
+    skipped: Vec<migration::Skipped>,
+    batches: migration::Batches,
+}
+
+impl Imports {
+    pub fn add(&mut self, text: &str) -> Result<()> {
+        if !text.trim().to_ascii_lowercase().starts_with(migration::SCHEME) {
+            self.entries.push(uri::parse(text)?);
+            return Ok(());
+        }
+
+        let payload = migration::parse(text)?;
+        if payload.batch_size > 1 {
+            info!("Read part {} of {} of a migration batch", payload.batch_index + 1, payload.batch_size);
+        }
+        self.batches.add(&payload);
+        self.entries.extend(payload.entries);
+        self.skipped.extend(payload.skipped);
+        Ok(())
+    }
+
+    /// Number of accounts left out of migration payloads.
+    pub fn skipped(&self) -> usize {
+        self.skipped.len()
+    }
+
+    /// Prints the accounts that were left out and the parts missing from
+    /// migration batches.
+    pub fn report(&self) {
+        for skipped in &self.skipped {
+            println!("Skipped {}: {}", skipped.name, skipped.reason);
+        }
+        for (size, missing) in self.batches.missing() {
+            let parts: Vec<String> = missing.iter().map(|index| (index + 1).to_string()).collect();
+            println!(
+                "Missing part {} of a migration batch of {}, import it to get the remaining entries.",
+                parts.join(", "), size
+            );
+        }
+    }
+}
+
+/// Saves an imported entry, never overwriting an existing one.
+pub fn save_new(name: &str, mut entry: Entry, password: &str) -> Result<()> {
+    if store::exists(name, password)? {
+        return Err(anyhow::anyhow!("Entry already exists: {}", name));
+    }
+
+    entry.created_at = Some(SystemClock.now()?);
+    store::save(name, &entry, password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_imports_add() {
+        let mut imports = Imports::default();
+        imports.add("otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(imports.entries.len(), 1);
+        assert_eq!(imports.entries[0].account.as_deref(), Some("alice"));
+
+        // An empty migration payload holds no entries
+        imports.add("OTPAUTH-MIGRATION://offline?data=").unwrap();
+        assert_eq!(imports.entries.len(), 1);
+        assert!(imports.add("https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_imports_skipped_and_missing_parts() {
+        // Batch of two with a single account named bob, missing its secret
+        let payload = [0x0a, 0x05, 0x12, 0x03, b'b', b'o', b'b', 3 << 3, 2, 4 << 3, 1];
+        let data = percent_encoding::utf8_percent_encode(
+            &base64::Engine::encode(&base64::engine::general_purpose::STANDARD, payload),
+            percent_encoding::NON_ALPHANUMERIC,
+        ).to_string();
+
+        let mut imports = Imports::default();
+        imports.add(&format!("otpauth-migration://offline?data={}", data)).unwrap();
+        assert!(imports.entries.is_empty());
+        assert_eq!(imports.skipped(), 1);
+        assert_eq!(imports.batches.missing(), vec![(2, vec![0])]);
+    }
+}
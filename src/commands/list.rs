@@ -1,26 +1,228 @@
 use anyhow::Result;
-use clap::{command, Args};
+use clap::{Args, ValueEnum};
+use std::collections::BTreeMap;
 
-use crate::core::file_system;
+use crate::{commands::args::VaultArgs, core::{clock, config::{self, StorageMode}, entry::Entry, keyring, names, store}};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Issuer,
+    Account,
+    Created,
+    LastUsed,
+}
 
 #[derive(Args, Debug)]
 #[command(about = "List all OTP secrets")]
-pub struct List {}
+pub struct List {
+    #[arg(help = "Only list the entries in this group, e.g. work/")]
+    group: Option<String>,
+
+    #[command(flatten)]
+    pub args: VaultArgs,
+
+    #[arg(long = "tag", help = "Only entries with this tag (repeatable, all must match)")]
+    tags: Vec<String>,
+
+    #[arg(long, help = "Only entries from this issuer")]
+    issuer: Option<String>,
+
+    #[arg(short, long, help = "Only entries whose name or metadata contains this text")]
+    search: Option<String>,
+
+    #[arg(long, value_enum, default_value = "name", help = "Sort order; entries without the field come last")]
+    sort: SortKey,
+
+    #[arg(short, long, help = "Show the metadata of each entry")]
+    long: bool,
+}
 
 impl List {
     pub fn run(&self) -> Result<()> {
-        let app_dir = file_system::get_app_data_dir().unwrap();
-        let secret_path = format!("{}/secrets/", app_dir);
+        let plain_listing = self.tags.is_empty() && self.issuer.is_none() && self.search.is_none()
+            && self.sort == SortKey::Name && !self.long;
+        let group = self.group.as_deref().unwrap_or_default();
+
+        // Per-entry files expose their names, anything else needs to decrypt
+        let entries: Vec<(String, Option<Entry>)> = if plain_listing && config::read().storage == StorageMode::Files {
+            store::list_file_names()?
+                .into_iter()
+                .filter(|name| names::in_group(name, group))
+                .map(|name| (name, None))
+                .collect()
+        } else {
+            let password = keyring::handle_password(&self.args)?;
+            let mut entries: Vec<(String, Entry)> = store::load_all(&password)?
+                .into_iter()
+                .filter(|(name, entry)| names::in_group(name, group) && self.filter(name, entry))
+                .collect();
+            sort_entries(&mut entries, self.sort);
+            entries.into_iter().map(|(name, entry)| (name, Some(entry))).collect()
+        };
 
-        let files_names = file_system::list_files_in_dir(&secret_path)?;
-        if files_names.is_empty() {
+        if entries.is_empty() {
             println!("No OTP secrets found.");
             return Ok(());
         }
         
-        for file_name in files_names {
-            println!("Found OTP secret: {}", file_name);
+        // A tree only makes sense in name order
+        if self.sort == SortKey::Name && !self.long {
+            let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+            for line in render_tree(&names) {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+
+        for (name, entry) in entries {
+            match entry {
+                Some(entry) if self.long => println!("{}", describe(&name, &entry)),
+                _ => println!("Found OTP secret: {}", name),
+            }
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn filter(&self, name: &str, entry: &Entry) -> bool {
+        self.tags.iter().all(|tag| entry.has_tag(tag))
+            && self.issuer.as_ref().is_none_or(|issuer| entry.issuer.as_ref().is_some_and(|i| i.eq_ignore_ascii_case(issuer)))
+            && self.search.as_ref().is_none_or(|query| entry.matches(name, query))
+    }
+}
+
+fn sort_entries(entries: &mut [(String, Entry)], key: SortKey) {
+    // `None` sorts first, so compare on "missing" before the value
+    fn missing_last<T: Ord>(value: Option<T>) -> (bool, Option<T>) {
+        (value.is_none(), value)
+    }
+
+    entries.sort_by(|(a_name, a), (b_name, b)| {
+        let order = match key {
+            SortKey::Name => std::cmp::Ordering::Equal,
+            SortKey::Issuer => missing_last(a.issuer.as_ref().map(|s| s.to_lowercase()))
+                .cmp(&missing_last(b.issuer.as_ref().map(|s| s.to_lowercase()))),
+            SortKey::Account => missing_last(a.account.as_ref().map(|s| s.to_lowercase()))
+                .cmp(&missing_last(b.account.as_ref().map(|s| s.to_lowercase()))),
+            SortKey::Created => missing_last(a.created_at).cmp(&missing_last(b.created_at)),
+            // Most recently used first
+            SortKey::LastUsed => missing_last(a.last_used_at.map(std::cmp::Reverse))
+                .cmp(&missing_last(b.last_used_at.map(std::cmp::Reverse))),
+        };
+        order.then_with(|| a_name.cmp(b_name))
+    });
+}
+
+/// Groups and entries below one group of the tree.
+#[derive(Default)]
+struct Node {
+    is_entry: bool,
+    children: BTreeMap<String, Node>,
+}
+
+/// Draws names like `work/aws/prod` as a tree, groups suffixed with `/`.
+fn render_tree(entry_names: &[&str]) -> Vec<String> {
+    fn render(node: &Node, prefix: &str, lines: &mut Vec<String>) {
+        // A name can be both an entry and a group, they are listed separately
+        let items: Vec<(String, Option<&Node>)> = node.children.iter()
+            .flat_map(|(segment, child)| {
+                let entry = child.is_entry.then(|| (segment.clone(), None));
+                let group = (!child.children.is_empty()).then(|| (format!("{}{}", segment, names::SEPARATOR), Some(child)));
+                entry.into_iter().chain(group)
+            })
+            .collect();
+
+        for (i, (label, group)) in items.iter().enumerate() {
+            let last = i + 1 == items.len();
+            lines.push(format!("{}{}{}", prefix, if last { "└── " } else { "├── " }, label));
+            if let Some(group) = group {
+                render(group, &format!("{}{}", prefix, if last { "    " } else { "│   " }), lines);
+            }
+        }
+    }
+
+    let mut root = Node::default();
+    for name in entry_names {
+        let node = name.split(names::SEPARATOR).fold(&mut root, |node, segment| {
+            node.children.entry(segment.to_string()).or_default()
+        });
+        node.is_entry = true;
+    }
+
+    let mut lines = Vec::new();
+    render(&root, "", &mut lines);
+    lines
+}
+
+fn describe(name: &str, entry: &Entry) -> String {
+    let mut line = name.to_string();
+    match (&entry.issuer, &entry.account) {
+        (Some(issuer), Some(account)) => line.push_str(&format!("  {} ({})", issuer, account)),
+        (Some(value), None) | (None, Some(value)) => line.push_str(&format!("  {}", value)),
+        (None, None) => {}
+    }
+    if !entry.tags.is_empty() {
+        line.push_str(&format!("  [{}]", entry.tags.join(", ")));
+    }
+    if let Some(url) = &entry.url {
+        line.push_str(&format!("  {}", url));
+    }
+    if let Some(created_at) = entry.created_at {
+        line.push_str(&format!("  created {}", clock::format_timestamp(created_at)));
+    }
+    if let Some(last_used_at) = entry.last_used_at {
+        line.push_str(&format!("  last used {}", clock::format_timestamp(last_used_at)));
+    }
+    if let Some(notes) = &entry.notes {
+        line.push_str(&format!("\n    {}", notes));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(issuer: Option<&str>, last_used_at: Option<u64>) -> Entry {
+        let mut entry = Entry::totp("JBSWY3DPEHPK3PXP");
+        entry.issuer = issuer.map(str::to_string);
+        entry.last_used_at = last_used_at;
+        entry
+    }
+
+    fn names(entries: &[(String, Entry)]) -> Vec<&str> {
+        entries.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_entries() {
+        let mut entries = vec![
+            ("c".to_string(), entry(None, Some(10))),
+            ("b".to_string(), entry(Some("github"), None)),
+            ("a".to_string(), entry(Some("Bank"), Some(20))),
+        ];
+
+        sort_entries(&mut entries, SortKey::Name);
+        assert_eq!(names(&entries), ["a", "b", "c"]);
+        sort_entries(&mut entries, SortKey::Issuer);
+        assert_eq!(names(&entries), ["a", "b", "c"]);
+        sort_entries(&mut entries, SortKey::LastUsed);
+        assert_eq!(names(&entries), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let lines = render_tree(&["github", "personal/bank", "work", "work/aws/prod", "work/aws/staging", "work/gitlab"]);
+        assert_eq!(lines, [
+            "├── github",
+            "├── personal/",
+            "│   └── bank",
+            "├── work",
+            "└── work/",
+            "    ├── aws/",
+            "    │   ├── prod",
+            "    │   └── staging",
+            "    └── gitlab",
+        ]);
+    }
+}
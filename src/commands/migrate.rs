@@ -0,0 +1,50 @@
+use anyhow::Result;
+use clap::Args;
+use log::{error, info};
+
+use crate::{commands::args::VaultArgs, core::{config::{self, StorageMode}, encryption::FORMAT_VERSION, keyring, store}};
+
+#[derive(Args, Debug)]
+#[command(about = "Rewrite entries stored in an older file format, or move them into the vault when storage is 'vault'")]
+pub struct Migrate {
+    #[command(flatten)]
+    pub args: VaultArgs,
+}
+
+impl Migrate {
+    pub fn run(&self) -> Result<()> {
+        let password = keyring::handle_password(&self.args)?;
+        let storage = config::read().storage;
+
+        let mut migrated = 0;
+        let mut failed = 0;
+        for name in store::list_file_names()? {
+            let result = match storage {
+                StorageMode::Files => store::migrate(&name, &password).map(|version| version.map(|version| {
+                    info!("Migrated {} from format {} to {}", name, version, FORMAT_VERSION);
+                })),
+                StorageMode::Vault => store::move_into_vault(&name, &password).map(|_| {
+                    info!("Moved {} into the vault", name);
+                    Some(())
+                }),
+            };
+            match result {
+                Ok(Some(())) => migrated += 1,
+                Ok(None) => {}
+                Err(e) => {
+                    error!("Failed to migrate {}: {}", name, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        match storage {
+            StorageMode::Files => println!("Migrated {} entries to format {}.", migrated, FORMAT_VERSION),
+            StorageMode::Vault => println!("Moved {} entries into the vault.", migrated),
+        }
+        if failed > 0 {
+            return Err(anyhow::anyhow!("{} entries could not be migrated", failed));
+        }
+        Ok(())
+    }
+}
@@ -1,17 +1,46 @@
 pub mod add;
 pub mod config;
+pub mod edit;
+pub mod export;
+pub mod export_qr;
+pub mod export_uri;
 pub mod get;
+pub mod import_qr;
+pub mod import_uri;
 pub mod list;
+pub mod migrate;
+pub mod new;
+pub mod prompt;
 pub mod remove;
+pub mod rename;
+pub mod resync;
+pub mod time;
+pub mod update;
+pub mod verify;
 pub mod args;
 
 use anyhow::Result;
-use clap::{command, Parser, Subcommand};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use crate::core::file_system::Location;
 use crate::commands::{
     add::AddEntry,
+    edit::EditEntry,
+    export::Export,
+    export_qr::ExportQr,
+    export_uri::ExportUri,
+    import_qr::ImportQr,
+    import_uri::ImportUri,
     remove::Remove,
+    rename::Rename,
+    update::UpdateSecret,
     list::List,
+    migrate::Migrate,
+    new::NewEntry,
     get::GetSecret,
+    resync::Resync,
+    time::Time,
+    verify::Verify,
     config::Config,
 };
 
@@ -21,25 +50,61 @@ use crate::commands::{
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    #[arg(long, global = true, help = "Directory of the vault and its config [env: OTP_TOOL_HOME] [default: XDG directories on Linux, ~/.otp-tool elsewhere]")]
+    pub vault: Option<PathBuf>,
+
+    #[arg(long, global = true, help = "Named profile with its own entries, config and stored password")]
+    pub profile: Option<String>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     Add(AddEntry),
+    New(NewEntry),
+    Edit(EditEntry),
+    ImportUri(ImportUri),
+    ImportQr(ImportQr),
+    ExportUri(ExportUri),
+    ExportQr(ExportQr),
+    Export(Export),
     Remove(Remove),
+    Rename(Rename),
+    Update(UpdateSecret),
     List(List),
     Get(GetSecret),
+    Resync(Resync),
+    Verify(Verify),
     Config(Config),
+    Migrate(Migrate),
+    Time(Time),
 }
 
 impl Cli {
+    pub fn location(&self) -> Location {
+        Location { vault: self.vault.clone(), profile: self.profile.clone() }
+    }
+
     pub fn run(&self) -> Result<()> {
         match &self.command {
             Commands::Add(cmd) => cmd.run(),
+            Commands::New(cmd) => cmd.run(),
+            Commands::Edit(cmd) => cmd.run(),
+            Commands::ImportUri(cmd) => cmd.run(),
+            Commands::ImportQr(cmd) => cmd.run(),
+            Commands::ExportUri(cmd) => cmd.run(),
+            Commands::ExportQr(cmd) => cmd.run(),
+            Commands::Export(cmd) => cmd.run(),
             Commands::Remove(cmd) => cmd.run(),
+            Commands::Rename(cmd) => cmd.run(),
+            Commands::Update(cmd) => cmd.run(),
             Commands::List(cmd) => cmd.run(),
             Commands::Get(cmd) => cmd.run(),
+            Commands::Resync(cmd) => cmd.run(),
+            Commands::Verify(cmd) => cmd.run(),
             Commands::Config(cmd) => cmd.run(),
+            Commands::Migrate(cmd) => cmd.run(),
+            Commands::Time(cmd) => cmd.run(),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,48 @@
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use anyhow::Result;
+use clap::Args;
+use log::info;
+
+use crate::{commands::args::{MetadataArgs, OtpParamArgs, PassArgs}, core::{base32, clock::{Clock, SystemClock}, config, entry::Entry, keyring, store, uri}};
+
+/// 160 bits, the secret length recommended by RFC 4226.
+const DEFAULT_SECRET_BYTES: u32 = 20;
+
+#[derive(Args, Debug)]
+#[command(about = "Generate a new random TOTP secret for <name> and print its otpauth:// URI")]
+pub struct NewEntry {
+    #[command(flatten)]
+    pub args: PassArgs,
+
+    #[arg(long, default_value_t = DEFAULT_SECRET_BYTES, value_parser = clap::value_parser!(u32).range(16..=64), help = "Secret length in bytes")]
+    length: u32,
+
+    #[command(flatten)]
+    params: OtpParamArgs,
+
+    #[command(flatten)]
+    metadata: MetadataArgs,
+}
+
+impl NewEntry {
+    pub fn run(&self) -> Result<()> {
+        let password = keyring::handle_password(&self.args)?;
+        if store::exists(&self.args.service_name, &password)? {
+            return Err(anyhow::anyhow!("Entry already exists: {}", self.args.service_name));
+        }
+
+        let mut secret = vec![0u8; self.length as usize];
+        OsRng.fill_bytes(&mut secret);
+
+        let mut entry = Entry::totp(&base32::encode(&secret));
+        self.params.apply(&mut entry);
+        self.metadata.apply(&mut entry);
+        entry.created_at = Some(SystemClock.now()?);
+        store::save(&self.args.service_name, &entry, &password)?;
+        info!("Added entry: {}", self.args.service_name);
+
+        let params = entry.params(&config::read());
+        println!("{}", uri::from_entry(&self.args.service_name, &entry, &params));
+        Ok(())
+    }
+}
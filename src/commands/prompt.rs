@@ -0,0 +1,70 @@
+use anyhow::Result;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::core::{names, store};
+
+/// Asks a yes/no question on the terminal. Without a terminal there is
+/// nobody to answer, so `default` is returned.
+pub fn confirm(question: &str, default: bool) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Ok(default);
+    }
+
+    print!("{} [{}] ", question, if default { "Y/n" } else { "y/N" });
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(match answer.trim().to_ascii_lowercase().as_str() {
+        "" => default,
+        answer => answer == "y" || answer == "yes",
+    })
+}
+
+/// Waits for Enter before going on, when there is a terminal to press it on.
+pub fn pause(message: &str) -> Result<()> {
+    if !io::stdin().is_terminal() {
+        return Ok(());
+    }
+
+    print!("{} ", message);
+    io::stdout().flush()?;
+    io::stdin().lock().read_line(&mut String::new())?;
+    Ok(())
+}
+
+/// Lets the user pick one of `options` by number. Returns `None` when the
+/// answer is empty or not a valid choice.
+pub fn select<'a>(question: &str, options: &[&'a str]) -> Result<Option<&'a str>> {
+    println!("{}", question);
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}) {}", i + 1, option);
+    }
+    print!("Select an entry [1-{}]: ", options.len());
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().parse::<usize>().ok()
+        .and_then(|choice| choice.checked_sub(1))
+        .and_then(|i| options.get(i).copied()))
+}
+
+/// Finds the entry meant by `query`, see `names::candidates`. Asks the user
+/// to choose when several entries match, or fails listing them when there
+/// is no terminal to ask on.
+pub fn resolve_service_name(query: &str, password: &str) -> Result<String> {
+    let entry_names = store::list_names(password)?;
+    let found = names::candidates(query, &entry_names);
+    match found.as_slice() {
+        [] => Err(anyhow::anyhow!("Service not found: {}", query)),
+        [name] => Ok(name.to_string()),
+        _ if !io::stdin().is_terminal() => Err(anyhow::anyhow!(
+            "'{}' matches several entries: {}", query, found.join(", ")
+        )),
+        _ => match select(&format!("Several entries match '{}':", query), &found)? {
+            Some(name) => Ok(name.to_string()),
+            None => Err(anyhow::anyhow!("No entry selected")),
+        },
+    }
+}
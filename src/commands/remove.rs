@@ -1,27 +1,82 @@
 use anyhow::Result;
-use clap::{command, Args};
-use crate::{commands::args::PassArgs, core::{file_system, keyring}};
+use clap::Args;
+use log::info;
+use std::io::{self, IsTerminal};
+use crate::{commands::{args::PassArgs, prompt}, core::{keyring, names, store}};
 
 #[derive(Args, Debug)]
-#[command(about = "Remove an existing OTP entry")]
+#[command(about = "Remove an existing OTP entry, or a whole group with -r")]
 pub struct Remove {
     #[command(flatten)]
     pub args: PassArgs,
+
+    #[arg(short, long, help = "Remove every entry in the group <name>")]
+    recursive: bool,
+
+    #[arg(short, long, help = "Remove without asking to confirm a group or a fuzzy match")]
+    yes: bool,
 }
 
 impl Remove {
     pub fn run(&self) -> Result<()> {
-        keyring::handle_password(&self.args)?;
-        if !file_system::exists(&self.args.service_name)? {
-            return Err(anyhow::anyhow!("Service not found: {}", self.args.service_name));
+        let password = keyring::handle_password(&self.args)?;
+        if self.recursive {
+            return self.remove_group(&password);
+        }
+
+        let service_name = prompt::resolve_service_name(&self.args.service_name, &password)?;
+        if service_name != self.args.service_name && !self.yes {
+            // Nobody can confirm a fuzzy match without a terminal
+            if !io::stdin().is_terminal() {
+                return Err(anyhow::anyhow!(
+                    "'{}' only matches {}, give the full name or -y to remove it", self.args.service_name, service_name
+                ));
+            }
+            if !prompt::confirm(&format!("Remove {}?", service_name), false)? {
+                println!("Nothing removed.");
+                return Ok(());
+            }
         }
 
-        let app_dir = file_system::get_app_data_dir().unwrap();
-        let secret_path = format!("{}/secrets/", app_dir);
-        
-        let file_path = format!("{}{}", secret_path, self.args.service_name);
-        file_system::delete_file(&file_path).unwrap();
+        store::remove(&service_name, &password)?;
+        info!("Removed entry: {}", service_name);
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn remove_group(&self, password: &str) -> Result<()> {
+        let group = &self.args.service_name;
+        if group.trim_end_matches(names::SEPARATOR).is_empty() {
+            return Err(anyhow::anyhow!("Refusing to remove every entry, name a group"));
+        }
+        let entries: Vec<String> = store::list_names(password)?
+            .into_iter()
+            .filter(|name| names::in_group(name, group))
+            .collect();
+        if entries.is_empty() {
+            return Err(anyhow::anyhow!("No entries found in group: {}", group));
+        }
+
+        for name in &entries {
+            println!("{}", name);
+        }
+        if !self.yes {
+            // Nobody can confirm the removal without a terminal
+            if !io::stdin().is_terminal() {
+                return Err(anyhow::anyhow!(
+                    "Removing the {} entries of {} needs -y without a terminal", entries.len(), group
+                ));
+            }
+            if !prompt::confirm(&format!("Remove these {} entries?", entries.len()), false)? {
+                println!("Nothing removed.");
+                return Ok(());
+            }
+        }
+
+        for name in &entries {
+            store::remove(name, password)?;
+        }
+        info!("Removed {} entries from {}", entries.len(), group);
+        Ok(())
+    }
+}
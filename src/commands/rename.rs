@@ -0,0 +1,27 @@
+use anyhow::Result;
+use clap::Args;
+use log::info;
+
+use crate::{commands::args::VaultArgs, core::{keyring, store}};
+
+#[derive(Args, Debug)]
+#[command(about = "Rename an existing OTP entry")]
+pub struct Rename {
+    #[arg(help = "Current name of the service")]
+    old_name: String,
+
+    #[arg(help = "New name of the service")]
+    new_name: String,
+
+    #[command(flatten)]
+    pub args: VaultArgs,
+}
+
+impl Rename {
+    pub fn run(&self) -> Result<()> {
+        let password = keyring::handle_password(&self.args)?;
+        store::rename(&self.old_name, &self.new_name, &password)?;
+        info!("Renamed entry {} to {}", self.old_name, self.new_name);
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::Result;
+use clap::Args;
+use log::info;
+
+use crate::{commands::args::PassArgs, core::{config, entry::OtpKind, keyring, oathtool, store}};
+
+const DEFAULT_LOOK_AHEAD: u64 = 100;
+
+#[derive(Args, Debug)]
+#[command(about = "Resynchronize the counter of an HOTP entry from two consecutive codes")]
+pub struct Resync {
+    #[command(flatten)]
+    pub args: PassArgs,
+
+    #[arg(long, help = "First code accepted by the server")]
+    code1: String,
+
+    #[arg(long, help = "Code following <code1>")]
+    code2: String,
+
+    #[arg(long, default_value_t = DEFAULT_LOOK_AHEAD, help = "Number of counter values to search ahead")]
+    look_ahead: u64,
+}
+
+impl Resync {
+    pub fn run(&self) -> Result<()> {
+        let password = keyring::handle_password(&self.args)?;
+
+        let config = config::read();
+
+        let counter = store::update(&self.args.service_name, &password, |entry| {
+            let params = entry.params(&config);
+            let OtpKind::Hotp { counter } = &mut entry.kind else {
+                return Err(anyhow::anyhow!("Entry is not an HOTP entry: {}", self.args.service_name));
+            };
+            let found = oathtool::resync_hotp(&entry.secret, *counter, self.look_ahead, &self.code1, &self.code2, &params)?;
+            match found {
+                Some(last) => {
+                    *counter = last + 1;
+                    Ok(*counter)
+                }
+                None => Err(anyhow::anyhow!(
+                    "Codes not found within {} steps of counter {}", self.look_ahead, counter
+                )),
+            }
+        })?;
+
+        info!("Resynchronized {}: next counter is {}", self.args.service_name, counter);
+        println!("Counter for {} set to {}", self.args.service_name, counter);
+        Ok(())
+    }
+}
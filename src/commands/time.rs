@@ -0,0 +1,70 @@
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use log::warn;
+use std::time::Duration;
+
+use crate::core::{config, time_sync};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Drift above which TOTP codes start failing on strict servers.
+const MAX_DRIFT: f64 = 5.0;
+
+#[derive(Args, Debug)]
+#[command(about = "Check the local clock used to generate codes")]
+pub struct Time {
+    #[command(subcommand)]
+    subcommand: TimeSubcommands,
+}
+
+#[derive(Subcommand, Debug)]
+enum TimeSubcommands {
+    Check(CheckTime),
+}
+
+impl Time {
+    pub fn run(&self) -> Result<()> {
+        match &self.subcommand {
+            TimeSubcommands::Check(check_time) => check_time.run(),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+#[command(about = "Compare the local clock with an SNTP server or an HTTP Date header")]
+struct CheckTime {
+    #[arg(long, conflicts_with = "url", help = "SNTP server as host:port [default: time_server from config]")]
+    server: Option<String>,
+
+    #[arg(long, help = "URL whose HTTP Date header is used [default: time_url from config]")]
+    url: Option<String>,
+
+    #[arg(long, help = "Store the measured offset as time_offset")]
+    save: bool,
+}
+
+impl CheckTime {
+    fn run(&self) -> Result<()> {
+        let mut cfg = config::read();
+        let url = self.url.clone().or_else(|| (!cfg.time_url.is_empty()).then(|| cfg.time_url.clone()));
+
+        let (source, offset) = match (&self.server, url) {
+            (None, Some(url)) => (url.clone(), time_sync::http_offset(&url, TIMEOUT)?),
+            (server, _) => {
+                let server = server.clone().unwrap_or_else(|| cfg.time_server.clone());
+                (server.clone(), time_sync::sntp_offset(&server, TIMEOUT)?)
+            }
+        };
+
+        let drift = offset - cfg.time_offset as f64;
+        println!("Local clock offset from {}: {:+.3}s", source, offset);
+        println!("Configured time_offset: {:+}s (remaining drift {:+.3}s)", cfg.time_offset, drift);
+        if self.save {
+            cfg.time_offset = offset.round() as i64;
+            config::write(&cfg);
+            println!("Saved time_offset: {:+}s", cfg.time_offset);
+        } else if drift.abs() > MAX_DRIFT {
+            warn!("Codes may be rejected, run `time check --save` to correct the clock");
+        }
+        Ok(())
+    }
+}
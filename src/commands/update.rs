@@ -0,0 +1,71 @@
+use anyhow::Result;
+use clap::Args;
+use log::info;
+
+use crate::{commands::{add::{self, OtpType}, args::PassArgs, prompt}, core::{clock::{Clock, SystemClock}, config, entry::OtpKind, keyring, secret::{self, SecretFormat}, store}};
+
+#[derive(Args, Debug)]
+#[command(about = "Replace the secret of an existing OTP entry, keeping the old one for `secret_history_days`")]
+pub struct UpdateSecret {
+    #[command(flatten)]
+    pub args: PassArgs,
+
+    #[arg(short, long, required_unless_present = "restore", conflicts_with = "restore", help = "New secret for the OTP entry")]
+    secret: Option<String>,
+
+    #[arg(long, value_enum, default_value = "base32", help = "Encoding of <secret>")]
+    secret_format: SecretFormat,
+
+    #[arg(short = 't', long = "type", value_enum, help = "Type of the OTP entry [default: unchanged]")]
+    otp_type: Option<OtpType>,
+
+    #[arg(long, default_value_t = 0, help = "Initial counter for HOTP entries")]
+    counter: u64,
+
+    #[arg(long, help = "Go back to the most recently replaced secret")]
+    restore: bool,
+
+    #[arg(short, long, help = "Save without asking to confirm the first code")]
+    yes: bool,
+}
+
+impl UpdateSecret {
+    pub fn run(&self) -> Result<()> {
+        let password = keyring::handle_password(&self.args)?;
+        let now = SystemClock.now()?;
+        let history_days = config::read().secret_history_days;
+
+        let Some(new_secret) = &self.secret else {
+            store::update(&self.args.service_name, &password, |entry| entry.restore_previous_secret(now))?;
+            info!("Restored the previous secret of {}", self.args.service_name);
+            return Ok(());
+        };
+        let new_secret = secret::normalize(new_secret, self.secret_format)?;
+
+        let mut entry = store::load(&self.args.service_name, &password)?;
+        let hotp = match self.otp_type {
+            Some(otp_type) => otp_type == OtpType::Hotp,
+            None => matches!(entry.kind, OtpKind::Hotp { .. }),
+        };
+        let kind = if hotp { OtpKind::Hotp { counter: self.counter } } else { OtpKind::Totp };
+
+        // Confirm before taking the lock, the prompt can take a while
+        entry.replace_secret(&new_secret, kind.clone(), now);
+        add::show_first_code(&self.args.service_name, &entry)?;
+        if !self.yes && !prompt::confirm("Does it match? Save the entry?", true)? {
+            println!("Secret not updated.");
+            return Ok(());
+        }
+
+        store::update(&self.args.service_name, &password, |entry| {
+            entry.replace_secret(&new_secret, kind, now);
+            Ok(())
+        })?;
+
+        info!(
+            "Updated the secret of {}, the previous one can be restored with --restore for {} days",
+            self.args.service_name, history_days
+        );
+        Ok(())
+    }
+}
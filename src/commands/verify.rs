@@ -0,0 +1,64 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::{commands::args::PassArgs, core::{clock::{self, Clock}, config, entry::OtpKind, keyring, oathtool, store}};
+
+#[derive(Args, Debug)]
+#[command(about = "Check a code against an entry, exiting with 1 when it does not match")]
+pub struct Verify {
+    #[command(flatten)]
+    pub args: PassArgs,
+
+    #[arg(short, long, help = "Code to verify")]
+    code: String,
+
+    #[arg(long, default_value_t = 1, help = "Number of time steps (or HOTP counters ahead) to accept")]
+    skew: u64,
+
+    #[arg(long, help = "Reject TOTP codes from a time step that was already accepted")]
+    replay_protection: bool,
+}
+
+impl Verify {
+    pub fn run(&self) -> Result<()> {
+        let password = keyring::handle_password(&self.args)?;
+        let entry = store::load(&self.args.service_name, &password)?;
+        let config = config::read();
+        let params = entry.params(&config);
+
+        match entry.kind {
+            OtpKind::Totp => {
+                let now = clock::configured(&config).now()?;
+                let Some((offset, step)) = oathtool::verify_totp(&entry.secret, &params, now, &self.code, self.skew)? else {
+                    return Err(anyhow::anyhow!("Invalid code for {}", self.args.service_name));
+                };
+                if self.replay_protection {
+                    // Check and record under the entry lock so two verifiers cannot both accept
+                    store::update(&self.args.service_name, &password, |entry| {
+                        if entry.last_accepted_step.is_some_and(|last| step <= last) {
+                            return Err(anyhow::anyhow!("Code for time step {} was already used", step));
+                        }
+                        entry.last_accepted_step = Some(step);
+                        Ok(())
+                    })?;
+                }
+                println!("Valid code for {}: time step {} (offset {:+})", self.args.service_name, step, offset);
+            }
+            // HOTP codes are single-use by design: the counter moves past the match
+            OtpKind::Hotp { .. } => {
+                let counter = store::update(&self.args.service_name, &password, |entry| {
+                    let OtpKind::Hotp { counter } = &mut entry.kind else {
+                        return Err(anyhow::anyhow!("Entry is no longer an HOTP entry"));
+                    };
+                    let Some(matched) = oathtool::verify_hotp(&entry.secret, &params, *counter, &self.code, self.skew)? else {
+                        return Err(anyhow::anyhow!("Invalid code for {}", self.args.service_name));
+                    };
+                    *counter = matched + 1;
+                    Ok(matched)
+                })?;
+                println!("Valid code for {}: counter {}", self.args.service_name, counter);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{Error, Result};
+
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Encodes RFC 4648 Base32 without `=` padding, as used in otpauth URIs.
+pub fn encode(data: &[u8]) -> String {
+    let mut output = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer: u64 = 0;
+    let mut bits = 0;
+
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u64;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        output.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    output
+}
+
+/// Decodes RFC 4648 Base32, ignoring case, whitespace and `=` padding.
+/// Trailing bits that do not fill a whole byte are dropped, like most
+/// authenticator apps do.
+pub fn decode(input: &str) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 5 / 8);
+    let mut buffer: u64 = 0;
+    let mut bits = 0;
+
+    for c in input.chars() {
+        if c.is_whitespace() || c == '=' {
+            continue;
+        }
+        let value = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u8 - b'A',
+            c @ '2'..='7' => c as u8 - b'2' + 26,
+            _ => return Err(Error::msg(format!("Invalid Base32 character: '{}'", c))),
+        };
+        buffer = (buffer << 5) | value as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_rfc4648_vectors() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "MY");
+        assert_eq!(encode(b"fo"), "MZXQ");
+        assert_eq!(encode(b"foo"), "MZXW6");
+        assert_eq!(encode(b"foob"), "MZXW6YQ");
+        assert_eq!(encode(b"fooba"), "MZXW6YTB");
+        assert_eq!(encode(b"foobar"), "MZXW6YTBOI");
+    }
+
+    #[test]
+    fn test_decode_rfc4648_vectors() {
+        assert_eq!(decode("MY======").unwrap(), b"f");
+        assert_eq!(decode("MZXQ====").unwrap(), b"fo");
+        assert_eq!(decode("MZXW6===").unwrap(), b"foo");
+        assert_eq!(decode("MZXW6YQ=").unwrap(), b"foob");
+        assert_eq!(decode("MZXW6YTB").unwrap(), b"fooba");
+        assert_eq!(decode("MZXW6YTBOI======").unwrap(), b"foobar");
+    }
+
+    #[test]
+    fn test_decode_is_case_and_whitespace_insensitive() {
+        assert_eq!(decode("jbsw y3dp ehpk 3pxp").unwrap(), decode("JBSWY3DPEHPK3PXP").unwrap());
+    }
+
+    #[test]
+    fn test_decode_invalid_character() {
+        assert!(decode("INVALID_BASE32_!!!").is_err());
+        assert!(decode("ABC1").is_err());
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let data: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(&encode(&data)).unwrap(), data);
+    }
+}
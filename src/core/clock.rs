@@ -0,0 +1,121 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::core::config::AppConfig;
+
+/// Source of the current time for code generation.
+pub trait Clock {
+    /// Seconds since the Unix epoch.
+    fn now(&self) -> Result<u64>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Result<u64> {
+        Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+    }
+}
+
+/// Clock stopped at a given timestamp, used for `get --at` and in tests.
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> Result<u64> {
+        Ok(self.0)
+    }
+}
+
+/// Clock shifted by a fixed number of seconds to correct local drift.
+pub struct OffsetClock<C: Clock> {
+    pub clock: C,
+    pub offset: i64,
+}
+
+impl<C: Clock> Clock for OffsetClock<C> {
+    fn now(&self) -> Result<u64> {
+        self.clock.now()?.checked_add_signed(self.offset).context(
+            "Time offset moves the clock before the Unix epoch"
+        )
+    }
+}
+
+/// System clock corrected by the configured `time_offset`.
+pub fn configured(config: &AppConfig) -> OffsetClock<SystemClock> {
+    OffsetClock { clock: SystemClock, offset: config.time_offset }
+}
+
+/// Parses an RFC 3339 date or a Unix timestamp in seconds.
+pub fn parse_timestamp(value: &str) -> Result<u64> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(seconds);
+    }
+    let date = DateTime::parse_from_rfc3339(value).with_context(
+        || format!("Invalid time '{}': expected RFC 3339 or Unix seconds", value)
+    )?;
+    u64::try_from(date.timestamp()).with_context(
+        || format!("Time is before the Unix epoch: {}", value)
+    )
+}
+
+pub fn format_timestamp(timestamp: u64) -> String {
+    match DateTime::from_timestamp(timestamp as i64, 0) {
+        Some(date) => date.to_rfc3339_opts(SecondsFormat::Secs, true),
+        None => timestamp.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_clock() {
+        assert_eq!(FixedClock(1234567890).now().unwrap(), 1234567890);
+    }
+
+    #[test]
+    fn test_system_clock_is_after_2020() {
+        assert!(SystemClock.now().unwrap() > 1577836800);
+    }
+
+    #[test]
+    fn test_offset_clock() {
+        let ahead = OffsetClock { clock: FixedClock(1000), offset: 30 };
+        let behind = OffsetClock { clock: FixedClock(1000), offset: -30 };
+        let invalid = OffsetClock { clock: FixedClock(10), offset: -30 };
+
+        assert_eq!(ahead.now().unwrap(), 1030);
+        assert_eq!(behind.now().unwrap(), 970);
+        assert!(invalid.now().is_err());
+    }
+
+    #[test]
+    fn test_configured_clock_uses_time_offset() {
+        let config = AppConfig { time_offset: -5, ..AppConfig::default() };
+        assert_eq!(configured(&config).offset, -5);
+    }
+
+    #[test]
+    fn test_parse_unix_timestamp() {
+        assert_eq!(parse_timestamp("1111111109").unwrap(), 1111111109);
+    }
+
+    #[test]
+    fn test_parse_rfc3339() {
+        assert_eq!(parse_timestamp("2005-03-18T01:58:29Z").unwrap(), 1111111109);
+        assert_eq!(parse_timestamp("2005-03-18T02:58:29+01:00").unwrap(), 1111111109);
+    }
+
+    #[test]
+    fn test_parse_invalid_time() {
+        assert!(parse_timestamp("yesterday").is_err());
+        assert!(parse_timestamp("1960-01-01T00:00:00Z").is_err());
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(1111111109), "2005-03-18T01:58:29Z");
+    }
+}
@@ -1,40 +1,140 @@
-use std::env;
 use anyhow::{Ok, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::core::{encryption::KdfParams, file_system};
+
 pub const APP_NAME: &str = "otp-tool";
 const DEFAULT_TIME: u32 = 30;
 const DEFAULT_DIGITS: u32 = 6;
 const REMAINING_WAIT_FOR_NEXT: u32 = 5;
+const DEFAULT_TIME_SERVER: &str = "pool.ntp.org:123";
+// OWASP recommended minimum for Argon2id
+const DEFAULT_KDF_MEMORY_KIB: u32 = 19 * 1024;
+const DEFAULT_KDF_ITERATIONS: u32 = 2;
+const DEFAULT_KDF_PARALLELISM: u32 = 1;
+const DEFAULT_SECRET_HISTORY_DAYS: u32 = 7;
+
+/// What `get` does when fewer than `wait_for_next_time` seconds are left.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WaitMode {
+    /// Block until the next time step and return its code
+    #[default]
+    Wait,
+    /// Show the current and the next code with a countdown
+    Both,
+}
+
+impl std::str::FromStr for WaitMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "wait" => Ok(WaitMode::Wait),
+            "both" => Ok(WaitMode::Both),
+            _ => Err(anyhow::anyhow!("Unknown wait mode: {} (expected 'wait' or 'both')", s)),
+        }
+    }
+}
+
+/// Where entries are kept on disk.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageMode {
+    /// One encrypted file per service under `secrets/`
+    #[default]
+    Files,
+    /// A single encrypted `vault.bin` holding every entry, names included
+    Vault,
+}
+
+impl std::str::FromStr for StorageMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "files" => Ok(StorageMode::Files),
+            "vault" => Ok(StorageMode::Vault),
+            _ => Err(anyhow::anyhow!("Unknown storage mode: {} (expected 'files' or 'vault')", s)),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
     pub duration: u32,
     pub digits: u32,
     pub wait_for_next_time: u32,
+    pub wait_mode: WaitMode,
+    /// Seconds added to the system clock before generating codes
+    pub time_offset: i64,
+    /// SNTP server used by `time check`
+    pub time_server: String,
+    /// When set, `time check` reads the HTTP `Date` header of this URL instead
+    pub time_url: String,
+    /// Argon2id cost used when encrypting; existing files keep their own
+    pub kdf_memory_kib: u32,
+    pub kdf_iterations: u32,
+    pub kdf_parallelism: u32,
+    pub storage: StorageMode,
+    /// Days a secret replaced by `update` is kept for `update --restore`
+    pub secret_history_days: u32,
+}
+
+impl AppConfig {
+    pub fn kdf_params(&self) -> KdfParams {
+        KdfParams {
+            memory_kib: self.kdf_memory_kib,
+            iterations: self.kdf_iterations,
+            parallelism: self.kdf_parallelism,
+        }
+    }
 }
 
 impl ::std::default::Default for AppConfig {
     fn default() -> Self { Self { 
         duration: DEFAULT_TIME,
         digits: DEFAULT_DIGITS,
-        wait_for_next_time: REMAINING_WAIT_FOR_NEXT
+        wait_for_next_time: REMAINING_WAIT_FOR_NEXT,
+        wait_mode: WaitMode::default(),
+        time_offset: 0,
+        time_server: DEFAULT_TIME_SERVER.to_string(),
+        time_url: String::new(),
+        kdf_memory_kib: DEFAULT_KDF_MEMORY_KIB,
+        kdf_iterations: DEFAULT_KDF_ITERATIONS,
+        kdf_parallelism: DEFAULT_KDF_PARALLELISM,
+        storage: StorageMode::default(),
+        secret_history_days: DEFAULT_SECRET_HISTORY_DAYS,
     } }
 }
 
 pub fn read() -> AppConfig {
-    let x = confy::load_path(get_config_path().unwrap()).unwrap();
+    let path = get_config_path().unwrap();
+    // confy would create a missing file with the default permissions
+    if !std::path::Path::new(&path).exists() {
+        write(&AppConfig::default());
+    }
+    let x = confy::load_path(path).unwrap();
     Ok(x)
         .unwrap_or_else(|_| AppConfig::default())
 }
 
-pub fn write(config: &AppConfig) -> () {
-    confy::store_path(get_config_path().unwrap(), config).unwrap();
+/// Written like the secrets: to a temporary file renamed into place and
+/// only readable by the owner.
+pub fn write(config: &AppConfig) {
+    let path = get_config_path().unwrap();
+    let temp_path = file_system::temp_path(&path);
+    if let Some(dir) = std::path::Path::new(&path).parent() {
+        file_system::create_dir_all(dir).unwrap();
+    }
+    confy::store_path(&temp_path, config).unwrap();
+    file_system::replace_with(&temp_path, &path).unwrap();
 }
 
+/// Each vault location and profile has its own config.
 fn get_config_path() -> Result<String> {
-    let mut path = env::home_dir().unwrap();
-    path.push(format!(".{APP_NAME}/{APP_NAME}.yaml"));
+    let path = std::path::Path::new(&file_system::get_config_dir()?).join(format!("{APP_NAME}.yaml"));
     match path.to_str() {
         Some(s) => Ok(s.to_string()),
         None => Err(anyhow::anyhow!("Failed to convert path to string")),
@@ -60,6 +160,15 @@ mod tests {
             duration: 60,
             digits: 8,
             wait_for_next_time: 10,
+            wait_mode: WaitMode::Both,
+            time_offset: -3,
+            time_server: "time.example.com:123".to_string(),
+            time_url: "https://example.com".to_string(),
+            kdf_memory_kib: 65536,
+            kdf_iterations: 3,
+            kdf_parallelism: 4,
+            storage: StorageMode::Vault,
+            secret_history_days: 30,
         };
         
         let serialized = serde_json::to_string(&config).unwrap();
@@ -68,6 +177,51 @@ mod tests {
         assert_eq!(config.duration, deserialized.duration);
         assert_eq!(config.digits, deserialized.digits);
         assert_eq!(config.wait_for_next_time, deserialized.wait_for_next_time);
+        assert_eq!(config.wait_mode, deserialized.wait_mode);
+        assert_eq!(config.time_offset, deserialized.time_offset);
+        assert_eq!(config.time_server, deserialized.time_server);
+        assert_eq!(config.time_url, deserialized.time_url);
+        assert_eq!(config.kdf_params(), deserialized.kdf_params());
+        assert_eq!(config.storage, deserialized.storage);
+        assert_eq!(config.secret_history_days, deserialized.secret_history_days);
+    }
+
+    #[test]
+    fn test_app_config_missing_fields_use_defaults() {
+        // Config files written by older versions lack newer keys
+        let config: AppConfig = serde_json::from_str(r#"{"duration": 60}"#).unwrap();
+
+        assert_eq!(config.duration, 60);
+        assert_eq!(config.digits, DEFAULT_DIGITS);
+        assert_eq!(config.wait_mode, WaitMode::Wait);
+        assert_eq!(config.time_offset, 0);
+        assert_eq!(config.time_server, DEFAULT_TIME_SERVER);
+        assert_eq!(config.storage, StorageMode::Files);
+        assert_eq!(config.secret_history_days, DEFAULT_SECRET_HISTORY_DAYS);
+    }
+
+    #[test]
+    fn test_kdf_params() {
+        let config = AppConfig::default();
+        let params = config.kdf_params();
+
+        assert_eq!(params.memory_kib, DEFAULT_KDF_MEMORY_KIB);
+        assert_eq!(params.iterations, DEFAULT_KDF_ITERATIONS);
+        assert_eq!(params.parallelism, DEFAULT_KDF_PARALLELISM);
+    }
+
+    #[test]
+    fn test_wait_mode_from_str() {
+        assert_eq!("wait".parse::<WaitMode>().unwrap(), WaitMode::Wait);
+        assert_eq!("both".parse::<WaitMode>().unwrap(), WaitMode::Both);
+        assert!("never".parse::<WaitMode>().is_err());
+    }
+
+    #[test]
+    fn test_storage_mode_from_str() {
+        assert_eq!("files".parse::<StorageMode>().unwrap(), StorageMode::Files);
+        assert_eq!("vault".parse::<StorageMode>().unwrap(), StorageMode::Vault);
+        assert!("cloud".parse::<StorageMode>().is_err());
     }
 
     #[test]
@@ -104,6 +258,8 @@ mod tests {
             duration: 45,
             digits: 8,
             wait_for_next_time: 7,
+            wait_mode: WaitMode::Wait,
+            ..AppConfig::default()
         };
         
         assert_eq!(config.duration, 45);
@@ -1,48 +1,215 @@
 use aes_gcm::{
-    aead::{generic_array::GenericArray, Aead, KeyInit, OsRng}, AeadCore, Aes256Gcm, Nonce
+    aead::{generic_array::GenericArray, rand_core::RngCore, Aead, KeyInit, OsRng}, AeadCore, Aes256Gcm, Nonce
 };
 use anyhow::{Error, Result};
+use argon2::{Argon2, Params, Version};
 use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
 
-#[derive(Serialize, Deserialize)]
+pub const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+/// Argon2id cost parameters.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+/// Magic bytes at the start of every versioned entry file.
+const MAGIC: &[u8; 4] = b"OTPV";
+pub const FORMAT_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherId {
+    Aes256Gcm,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    /// First 32 password bytes, zero padded. Only found in unversioned files.
+    PasswordBytes,
+    Argon2id(KdfParams),
+}
+
+/// Self-describing encrypted payload, stored as `MAGIC`, the format version
+/// byte and the bincode of the remaining fields.
+#[derive(Serialize, Deserialize, Debug)]
 pub struct EncryptedSecret {
+    #[serde(skip)]
+    version: u8,
+    cipher: CipherId,
+    kdf: Kdf,
+    salt: Vec<u8>,
     nonce: Vec<u8>,
     ciphertext: Vec<u8>,
 }
 
-pub fn encrypt(plain: &str, password: &str) -> Result<EncryptedSecret> {
-    let key = GenericArray::from_slice(&password.as_bytes()[..32]);
-    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-    let cipher = Aes256Gcm::new(key);
+/// Files written before the format was versioned: `{nonce, ciphertext}`
+/// keyed by the password bytes.
+#[derive(Deserialize)]
+struct LegacySecret {
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
 
-    let ciphertext = cipher.encrypt(&nonce, plain.as_ref());
+impl EncryptedSecret {
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Whether the file should be rewritten in the current format.
+    pub fn needs_migration(&self) -> bool {
+        self.version < FORMAT_VERSION
+    }
 
-    if ciphertext.is_err() {
-        return Err(Error::msg(format!("Encryption failed: {}", ciphertext.unwrap_err())));
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(FORMAT_VERSION);
+        bytes.extend(bincode::serialize(self)?);
+        Ok(bytes)
     }
 
-    Ok(EncryptedSecret { nonce: nonce.to_vec(), ciphertext: ciphertext.unwrap() })
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if let Some(rest) = bytes.strip_prefix(MAGIC) {
+            let (&version, body) = rest.split_first().ok_or_else(
+                || Error::msg("Truncated entry file")
+            )?;
+            if version != FORMAT_VERSION {
+                return Err(Error::msg(format!(
+                    "Unsupported entry format version {} (this otp-tool reads version {})", version, FORMAT_VERSION
+                )));
+            }
+            let mut encrypted: EncryptedSecret = bincode::deserialize(body)?;
+            encrypted.version = version;
+            return Ok(encrypted);
+        }
+
+        let legacy: LegacySecret = bincode::deserialize(bytes).map_err(
+            |_| Error::msg("Unrecognized entry file format")
+        )?;
+        Ok(EncryptedSecret {
+            version: 0,
+            cipher: CipherId::Aes256Gcm,
+            kdf: Kdf::PasswordBytes,
+            salt: Vec::new(),
+            nonce: legacy.nonce,
+            ciphertext: legacy.ciphertext,
+        })
+    }
 }
 
-pub fn decrypt(encrypted: &EncryptedSecret, password: &str) -> Result<String> {
-    let key = GenericArray::from_slice(&password.as_bytes()[..32]);
-    let cipher = Aes256Gcm::new(key);
+/// Password digest, parameters and salt a key was derived from.
+type KeyCacheEntry = ([u8; 32], KdfParams, Vec<u8>, [u8; KEY_LEN]);
+
+/// Derived keys for this process, so a vault salt costs one derivation per
+/// password instead of one per entry.
+static KEY_CACHE: Mutex<Vec<KeyCacheEntry>> = Mutex::new(Vec::new());
+
+pub fn generate_salt() -> Vec<u8> {
+    let mut salt = vec![0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// Digest to tell passwords apart in caches without keeping them in memory.
+pub fn password_digest(password: &str) -> [u8; 32] {
+    Sha256::digest(password.as_bytes()).into()
+}
 
-    let nonce = Nonce::from_slice(&encrypted.nonce);
-    let encrypted_text = bincode::serialize(&encrypted.ciphertext).unwrap();
-    let decrypted_bytes = cipher.decrypt(&nonce, encrypted_text.as_ref());
-    if decrypted_bytes.is_err() {
-        return Err(Error::msg(format!("Decryption failed: {}", decrypted_bytes.unwrap_err())));
+pub fn derive_key(password: &str, salt: &[u8], kdf: &KdfParams) -> Result<[u8; KEY_LEN]> {
+    let password_digest = password_digest(password);
+    let mut cache = KEY_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, _, _, key)) = cache.iter().find(
+        |(digest, params, cached_salt, _)| *digest == password_digest && params == kdf && cached_salt == salt
+    ) {
+        return Ok(*key);
     }
-    let secret_restored = String::from_utf8(decrypted_bytes.unwrap()).unwrap();
+
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(KEY_LEN)).map_err(
+        |e| Error::msg(format!("Invalid key derivation parameters: {}", e))
+    )?;
+    let mut key = [0u8; KEY_LEN];
+    Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::msg(format!("Key derivation failed: {}", e)))?;
+
+    cache.push((password_digest, *kdf, salt.to_vec(), key));
+    Ok(key)
+}
+
+fn legacy_key(password: &str) -> [u8; KEY_LEN] {
+    // Take the first 32 bytes of the password, padding with zeros
+    let mut key = [0u8; KEY_LEN];
+    let bytes = password.as_bytes();
+    let len = bytes.len().min(key.len());
+    key[..len].copy_from_slice(&bytes[..len]);
+    key
+}
+
+fn seal(plain: &str, key: &[u8; KEY_LEN]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(key));
+
+    let ciphertext = cipher.encrypt(&nonce, plain.as_ref()).map_err(
+        |e| Error::msg(format!("Encryption failed: {}", e))
+    )?;
+    Ok((nonce.to_vec(), ciphertext))
+}
+
+fn open(nonce: &[u8], ciphertext: &[u8], key: &[u8; KEY_LEN]) -> Result<String> {
+    if nonce.len() != NONCE_LEN {
+        return Err(Error::msg("Decryption failed: invalid nonce"));
+    }
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(key));
+
+    let decrypted_bytes = cipher.decrypt(Nonce::from_slice(nonce), ciphertext).map_err(
+        |e| Error::msg(format!("Decryption failed: {}", e))
+    )?;
+    let secret_restored = String::from_utf8(decrypted_bytes)?;
 
     Ok(secret_restored)
 }
 
+pub fn encrypt(plain: &str, password: &str, kdf: &KdfParams, salt: &[u8]) -> Result<EncryptedSecret> {
+    let key = derive_key(password, salt, kdf)?;
+    let (nonce, ciphertext) = seal(plain, &key)?;
+
+    Ok(EncryptedSecret {
+        version: FORMAT_VERSION,
+        cipher: CipherId::Aes256Gcm,
+        kdf: Kdf::Argon2id(*kdf),
+        salt: salt.to_vec(),
+        nonce,
+        ciphertext,
+    })
+}
+
+pub fn decrypt(encrypted: &EncryptedSecret, password: &str) -> Result<String> {
+    let key = match &encrypted.kdf {
+        Kdf::PasswordBytes => legacy_key(password),
+        Kdf::Argon2id(params) => derive_key(password, &encrypted.salt, params)?,
+    };
+    match encrypted.cipher {
+        CipherId::Aes256Gcm => open(&encrypted.nonce, &encrypted.ciphertext, &key),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // Cheapest parameters Argon2 accepts, to keep tests fast
+    const TEST_KDF: KdfParams = KdfParams { memory_kib: 8, iterations: 1, parallelism: 1 };
+    const TEST_SALT: &[u8; SALT_LEN] = b"0123456789abcdef";
+
+    fn encrypt(plain: &str, password: &str) -> Result<EncryptedSecret> {
+        super::encrypt(plain, password, &TEST_KDF, TEST_SALT)
+    }
+
     #[test]
     fn test_encrypt_decrypt_success() {
         let plain_text = "test_secret_key_123";
@@ -72,9 +239,117 @@ mod tests {
         let plain_text = "test_secret";
         let short_password = "short";
         
-        // This should work since we take the first 32 bytes and pad with zeros
+        // Any password length works since the key is derived from it
         let result = encrypt(plain_text, short_password);
         assert!(result.is_ok());
+        assert_eq!(decrypt(&result.unwrap(), short_password).unwrap(), plain_text);
+    }
+
+    #[test]
+    fn test_encrypt_with_empty_and_long_passwords() {
+        let plain_text = "test_secret";
+        let long_password = "p".repeat(200);
+
+        for password in ["", long_password.as_str()] {
+            let encrypted = encrypt(plain_text, password).unwrap();
+            assert_eq!(decrypt(&encrypted, password).unwrap(), plain_text);
+        }
+    }
+
+    #[test]
+    fn test_passwords_sharing_a_prefix_fail() {
+        // The first 32 bytes used to be the whole key
+        let plain_text = "test_secret";
+        let password1 = "this_is_a_very_long_password_32chars_one";
+        let password2 = "this_is_a_very_long_password_32chars_two";
+
+        let encrypted = encrypt(plain_text, password1).unwrap();
+        assert!(decrypt(&encrypted, password2).is_err());
+    }
+
+    #[test]
+    fn test_kdf_params_and_salt_are_stored() {
+        let params = KdfParams { memory_kib: 16, iterations: 2, parallelism: 1 };
+        let salt = generate_salt();
+
+        let encrypted = super::encrypt("test_secret", "password", &params, &salt).unwrap();
+        let restored = EncryptedSecret::from_bytes(&encrypted.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored.kdf, Kdf::Argon2id(params));
+        assert_eq!(restored.salt, salt);
+        assert_eq!(decrypt(&restored, "password").unwrap(), "test_secret");
+    }
+
+    #[test]
+    fn test_derive_key_depends_on_salt_and_params() {
+        let other_salt = b"fedcba9876543210";
+        let other_params = KdfParams { iterations: 2, ..TEST_KDF };
+        let key = derive_key("password", TEST_SALT, &TEST_KDF).unwrap();
+
+        assert_eq!(key, derive_key("password", TEST_SALT, &TEST_KDF).unwrap());
+        assert_ne!(key, derive_key("password", other_salt, &TEST_KDF).unwrap());
+        assert_ne!(key, derive_key("password", TEST_SALT, &other_params).unwrap());
+    }
+
+    #[test]
+    fn test_derive_key_invalid_params() {
+        let params = KdfParams { memory_kib: 1, iterations: 0, parallelism: 0 };
+        assert!(derive_key("password", TEST_SALT, &params).is_err());
+    }
+
+    #[test]
+    fn test_generate_salt() {
+        let salt1 = generate_salt();
+        let salt2 = generate_salt();
+
+        assert_eq!(salt1.len(), SALT_LEN);
+        assert_ne!(salt1, salt2);
+    }
+
+    #[test]
+    fn test_container_header() {
+        let encrypted = encrypt("test_secret", "password").unwrap();
+        let bytes = encrypted.to_bytes().unwrap();
+
+        assert!(bytes.starts_with(MAGIC));
+        assert_eq!(bytes[MAGIC.len()], FORMAT_VERSION);
+        assert_eq!(encrypted.version(), FORMAT_VERSION);
+        assert!(!encrypted.needs_migration());
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_newer_version() {
+        let mut bytes = encrypt("test_secret", "password").unwrap().to_bytes().unwrap();
+        bytes[MAGIC.len()] = FORMAT_VERSION + 1;
+
+        let error = EncryptedSecret::from_bytes(&bytes).unwrap_err();
+        assert!(error.to_string().contains("Unsupported entry format version"));
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_garbage() {
+        assert!(EncryptedSecret::from_bytes(b"OTPV").is_err());
+        assert!(EncryptedSecret::from_bytes(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_legacy_format() {
+        let password = "this_is_a_very_long_password_32chars";
+        let (nonce, ciphertext) = seal("test_secret", &legacy_key(password)).unwrap();
+        let bytes = bincode::serialize(&(nonce, ciphertext)).unwrap();
+
+        let encrypted = EncryptedSecret::from_bytes(&bytes).unwrap();
+        assert_eq!(encrypted.kdf, Kdf::PasswordBytes);
+        assert!(encrypted.needs_migration());
+        assert_eq!(decrypt(&encrypted, password).unwrap(), "test_secret");
+        assert!(decrypt(&encrypted, "this_is_a_different_password_32chars").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_invalid_nonce() {
+        let mut encrypted = encrypt("test_secret", "password").unwrap();
+        encrypted.nonce.truncate(4);
+
+        assert!(decrypt(&encrypted, "password").is_err());
     }
 
     #[test]
@@ -105,8 +380,8 @@ mod tests {
         let encrypted = encrypt(plain_text, password).unwrap();
         
         // Test that EncryptedSecret can be serialized and deserialized
-        let serialized = bincode::serialize(&encrypted).unwrap();
-        let deserialized: EncryptedSecret = bincode::deserialize(&serialized).unwrap();
+        let serialized = encrypted.to_bytes().unwrap();
+        let deserialized = EncryptedSecret::from_bytes(&serialized).unwrap();
         
         let decrypted = decrypt(&deserialized, password).unwrap();
         assert_eq!(decrypted, plain_text);
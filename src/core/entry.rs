@@ -0,0 +1,292 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{config::AppConfig, oathtool::{Algorithm, OtpParams}};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum OtpKind {
+    Totp,
+    Hotp { counter: u64 },
+}
+
+/// A secret replaced by `update`, kept in case the new enrollment failed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PreviousSecret {
+    pub secret: String,
+    pub kind: OtpKind,
+    pub digits: Option<u32>,
+    pub period: Option<u64>,
+    pub algorithm: Option<Algorithm>,
+    pub t0: Option<u64>,
+    /// Unix seconds
+    pub replaced_at: u64,
+}
+
+/// Everything stored encrypted for a single service.
+///
+/// Parameters left as `None` fall back to the configuration defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub secret: String,
+    pub kind: OtpKind,
+    pub digits: Option<u32>,
+    pub period: Option<u64>,
+    pub algorithm: Option<Algorithm>,
+    pub t0: Option<u64>,
+    /// Last TOTP time step accepted by `verify` with replay protection
+    pub last_accepted_step: Option<u64>,
+    pub issuer: Option<String>,
+    /// Account or username at the issuer
+    pub account: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+    pub url: Option<String>,
+    /// Unix seconds
+    pub created_at: Option<u64>,
+    /// Unix seconds of the last `get` of an HOTP code
+    pub last_used_at: Option<u64>,
+    /// Replaced secrets, oldest first
+    #[serde(default)]
+    pub secret_history: Vec<PreviousSecret>,
+}
+
+impl Entry {
+    pub fn totp(secret: &str) -> Self {
+        Entry::new(secret, OtpKind::Totp)
+    }
+
+    pub fn hotp(secret: &str, counter: u64) -> Self {
+        Entry::new(secret, OtpKind::Hotp { counter })
+    }
+
+    fn new(secret: &str, kind: OtpKind) -> Self {
+        Entry {
+            secret: secret.to_string(),
+            kind,
+            digits: None,
+            period: None,
+            algorithm: None,
+            t0: None,
+            last_accepted_step: None,
+            issuer: None,
+            account: None,
+            tags: Vec::new(),
+            notes: None,
+            url: None,
+            created_at: None,
+            last_used_at: None,
+            secret_history: Vec::new(),
+        }
+    }
+
+    /// Resolves the parameters used to generate codes for this entry.
+    pub fn params(&self, config: &AppConfig) -> OtpParams {
+        OtpParams {
+            digits: self.digits.unwrap_or(config.digits),
+            period: self.period.unwrap_or(config.duration as u64),
+            t0: self.t0.unwrap_or(0),
+            algorithm: self.algorithm.unwrap_or_default(),
+        }
+    }
+
+    /// Switches to a new secret, keeping the current one and its parameters
+    /// in the history.
+    pub fn replace_secret(&mut self, secret: &str, kind: OtpKind, now: u64) {
+        let previous = PreviousSecret {
+            secret: std::mem::replace(&mut self.secret, secret.to_string()),
+            kind: std::mem::replace(&mut self.kind, kind),
+            digits: self.digits,
+            period: self.period,
+            algorithm: self.algorithm,
+            t0: self.t0,
+            replaced_at: now,
+        };
+        self.secret_history.push(previous);
+        self.last_accepted_step = None;
+    }
+
+    /// Goes back to the most recently replaced secret. The current secret
+    /// takes its place in the history so the restore can be undone.
+    pub fn restore_previous_secret(&mut self, now: u64) -> Result<()> {
+        let previous = self.secret_history.pop().ok_or_else(
+            || anyhow::anyhow!("No previous secret to restore")
+        )?;
+        self.replace_secret(&previous.secret, previous.kind, now);
+        self.digits = previous.digits;
+        self.period = previous.period;
+        self.algorithm = previous.algorithm;
+        self.t0 = previous.t0;
+        Ok(())
+    }
+
+    /// Drops history items replaced more than `days` days before `now`.
+    pub fn prune_secret_history(&mut self, now: u64, days: u32) {
+        let max_age = days as u64 * 24 * 60 * 60;
+        self.secret_history.retain(|previous| now.saturating_sub(previous.replaced_at) <= max_age);
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// Case-insensitive substring search over the name and text metadata.
+    pub fn matches(&self, name: &str, query: &str) -> bool {
+        let query = query.to_lowercase();
+        [Some(name), self.issuer.as_deref(), self.account.as_deref(), self.notes.as_deref(), self.url.as_deref()]
+            .into_iter()
+            .flatten()
+            .chain(self.tags.iter().map(String::as_str))
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+
+    pub fn to_plain(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parses a decrypted payload. Entries written before the entry format
+    /// existed only contain the bare Base32 secret and are read as TOTP.
+    pub fn from_plain(plain: &str) -> Self {
+        serde_json::from_str(plain).unwrap_or_else(|_| Entry::totp(plain.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_roundtrip() {
+        let mut entry = Entry::hotp("JBSWY3DPEHPK3PXP", 42);
+        entry.digits = Some(8);
+        entry.algorithm = Some(Algorithm::Sha512);
+        let plain = entry.to_plain().unwrap();
+        assert_eq!(Entry::from_plain(&plain), entry);
+    }
+
+    #[test]
+    fn test_entry_from_legacy_secret() {
+        let entry = Entry::from_plain("JBSWY3DPEHPK3PXP");
+        assert_eq!(entry, Entry::totp("JBSWY3DPEHPK3PXP"));
+    }
+
+    #[test]
+    fn test_entry_from_numeric_legacy_secret() {
+        // A Base32 secret made only of digits is valid JSON, but not an entry
+        let entry = Entry::from_plain("234567");
+        assert_eq!(entry, Entry::totp("234567"));
+    }
+
+    #[test]
+    fn test_entry_without_parameters() {
+        // Entries written before per-entry parameters existed
+        let entry = Entry::from_plain(r#"{"secret":"JBSWY3DPEHPK3PXP","kind":"Totp"}"#);
+        assert_eq!(entry, Entry::totp("JBSWY3DPEHPK3PXP"));
+    }
+
+    #[test]
+    fn test_params_fall_back_to_config() {
+        let config = AppConfig { duration: 60, digits: 8, ..AppConfig::default() };
+        let params = Entry::totp("JBSWY3DPEHPK3PXP").params(&config);
+
+        assert_eq!(params, OtpParams { digits: 8, period: 60, t0: 0, algorithm: Algorithm::Sha1 });
+    }
+
+    #[test]
+    fn test_params_prefer_entry_values() {
+        let config = AppConfig::default();
+        let mut entry = Entry::totp("JBSWY3DPEHPK3PXP");
+        entry.digits = Some(7);
+        entry.period = Some(45);
+        entry.algorithm = Some(Algorithm::Sha256);
+        entry.t0 = Some(100);
+
+        let params = entry.params(&config);
+        assert_eq!(params, OtpParams { digits: 7, period: 45, t0: 100, algorithm: Algorithm::Sha256 });
+    }
+
+    #[test]
+    fn test_entry_without_metadata() {
+        // Entries written before metadata existed
+        let entry = Entry::from_plain(r#"{"secret":"JBSWY3DPEHPK3PXP","kind":"Totp","digits":null,"period":null,"algorithm":null,"t0":null,"last_accepted_step":null}"#);
+        assert_eq!(entry, Entry::totp("JBSWY3DPEHPK3PXP"));
+        assert!(entry.tags.is_empty());
+    }
+
+    #[test]
+    fn test_metadata_roundtrip() {
+        let mut entry = Entry::totp("JBSWY3DPEHPK3PXP");
+        entry.issuer = Some("GitHub".to_string());
+        entry.account = Some("alice@example.com".to_string());
+        entry.tags = vec!["work".to_string(), "dev".to_string()];
+        entry.created_at = Some(1700000000);
+        assert_eq!(Entry::from_plain(&entry.to_plain().unwrap()), entry);
+    }
+
+    #[test]
+    fn test_matches_and_has_tag() {
+        let mut entry = Entry::totp("JBSWY3DPEHPK3PXP");
+        entry.issuer = Some("GitHub".to_string());
+        entry.account = Some("alice@example.com".to_string());
+        entry.tags = vec!["Work".to_string()];
+
+        assert!(entry.matches("gh-work", "github"));
+        assert!(entry.matches("gh-work", "ALICE"));
+        assert!(entry.matches("gh-work", "gh-"));
+        assert!(entry.matches("gh-work", "wor"));
+        assert!(!entry.matches("gh-work", "gitlab"));
+        assert!(entry.has_tag("work"));
+        assert!(!entry.has_tag("personal"));
+    }
+
+    #[test]
+    fn test_replace_and_restore_secret() {
+        let mut entry = Entry::hotp("JBSWY3DPEHPK3PXP", 12);
+        entry.digits = Some(8);
+        entry.algorithm = Some(Algorithm::Sha256);
+        entry.replace_secret("GEZDGNBVGY3TQOJQ", OtpKind::Totp, 1000);
+
+        assert_eq!(entry.secret, "GEZDGNBVGY3TQOJQ");
+        assert_eq!(entry.kind, OtpKind::Totp);
+        assert_eq!(entry.secret_history, [PreviousSecret {
+            secret: "JBSWY3DPEHPK3PXP".to_string(),
+            kind: OtpKind::Hotp { counter: 12 },
+            digits: Some(8),
+            period: None,
+            algorithm: Some(Algorithm::Sha256),
+            t0: None,
+            replaced_at: 1000,
+        }]);
+
+        // The new secret came with other parameters
+        entry.digits = None;
+        entry.period = Some(60);
+        entry.algorithm = None;
+        entry.restore_previous_secret(2000).unwrap();
+        assert_eq!(entry.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(entry.kind, OtpKind::Hotp { counter: 12 });
+        assert_eq!((entry.digits, entry.period, entry.algorithm), (Some(8), None, Some(Algorithm::Sha256)));
+        assert_eq!(entry.secret_history.len(), 1);
+        assert_eq!(entry.secret_history[0].secret, "GEZDGNBVGY3TQOJQ");
+        assert_eq!(entry.secret_history[0].period, Some(60));
+
+        entry.secret_history.clear();
+        assert!(entry.restore_previous_secret(3000).is_err());
+    }
+
+    #[test]
+    fn test_prune_secret_history() {
+        let day = 24 * 60 * 60;
+        let mut entry = Entry::totp("JBSWY3DPEHPK3PXP");
+        entry.replace_secret("GEZDGNBVGY3TQOJQ", OtpKind::Totp, 0);
+        entry.replace_secret("MZXW6YTBOI", OtpKind::Totp, 5 * day);
+
+        entry.prune_secret_history(7 * day, 7);
+        assert_eq!(entry.secret_history.len(), 2);
+        entry.prune_secret_history(8 * day, 7);
+        assert_eq!(entry.secret_history.len(), 1);
+        assert_eq!(entry.secret_history[0].secret, "GEZDGNBVGY3TQOJQ");
+        entry.prune_secret_history(8 * day, 0);
+        assert!(entry.secret_history.is_empty());
+    }
+}
@@ -1,6 +1,277 @@
 use anyhow::{Context, Result};
-use std::{env, fs::File, io::{Read, Write}};
-use crate::core::config::{APP_NAME};
+use std::{env, fs::{DirBuilder, File, OpenOptions, TryLockError}, io::{ErrorKind, Read, Write}, path::{Path, PathBuf}, sync::OnceLock, thread, time::Duration};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use sha2::{Digest, Sha256};
+use crate::core::{config::APP_NAME, names};
+
+const LOCK_RETRIES: u32 = 50;
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Environment variable overriding the default directories.
+pub const HOME_ENV: &str = "OTP_TOOL_HOME";
+
+/// Where the app data lives, chosen once from the command line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    /// Replaces `OTP_TOOL_HOME` and the default directories
+    pub vault: Option<PathBuf>,
+    /// Keeps entries, config and the stored password apart under `profiles/<name>`
+    pub profile: Option<String>,
+}
+
+static LOCATION: OnceLock<Location> = OnceLock::new();
+
+pub fn set_location(location: Location) -> Result<()> {
+    if let Some(profile) = &location.profile {
+        // Same rules as a single group of an entry name
+        if names::validate(profile).is_err() || profile.contains(names::SEPARATOR) {
+            return Err(anyhow::anyhow!("Invalid profile name: {}", profile));
+        }
+    }
+    LOCATION.set(location).map_err(|_| anyhow::anyhow!("The vault location is already set"))
+}
+
+fn location() -> &'static Location {
+    LOCATION.get_or_init(Location::default)
+}
+
+/// Whether neither `--vault`, `--profile` nor `OTP_TOOL_HOME` are used.
+pub fn is_default_location() -> bool {
+    *location() == Location::default() && env::var_os(HOME_ENV).is_none_or(|home| home.is_empty())
+}
+
+/// Directories used for the current location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppDirs {
+    /// Entries, the vault and its salt
+    pub data: PathBuf,
+    pub config: PathBuf,
+    /// Lock files; when `None` they go next to the locked file
+    pub runtime: Option<PathBuf>,
+}
+
+/// Base directories of the platform. The XDG ones are only used on Linux,
+/// elsewhere everything stays in `~/.otp-tool`.
+#[derive(Debug, Default)]
+struct SystemDirs {
+    home: Option<PathBuf>,
+    data: Option<PathBuf>,
+    config: Option<PathBuf>,
+    runtime: Option<PathBuf>,
+}
+
+fn system_dirs() -> SystemDirs {
+    let mut system = SystemDirs { home: dirs::home_dir(), ..SystemDirs::default() };
+    if cfg!(target_os = "linux") {
+        system.data = dirs::data_dir();
+        system.config = dirs::config_dir();
+        system.runtime = dirs::runtime_dir();
+    }
+    system
+}
+
+fn legacy_dir(system: &SystemDirs) -> Result<PathBuf> {
+    let home = system.home.as_ref().with_context(|| "Failed to get home directory")?;
+    Ok(home.join(format!(".{}", APP_NAME)))
+}
+
+fn resolve_app_dirs(location: &Location, env_home: Option<PathBuf>, system: &SystemDirs) -> Result<AppDirs> {
+    let with_profile = |dir: PathBuf| match &location.profile {
+        Some(profile) => dir.join("profiles").join(profile),
+        None => dir,
+    };
+
+    let custom = location.vault.clone().or(env_home.filter(|home| !home.as_os_str().is_empty()));
+    let single_dir = match (custom, &system.data, &system.config) {
+        (Some(custom), _, _) => custom,
+        (None, Some(data), Some(config)) => return Ok(AppDirs {
+            data: with_profile(data.join(APP_NAME)),
+            config: with_profile(config.join(APP_NAME)),
+            runtime: system.runtime.as_ref().map(|runtime| runtime.join(APP_NAME)),
+        }),
+        _ => legacy_dir(system)?,
+    };
+    let dir = with_profile(single_dir);
+    Ok(AppDirs { data: dir.clone(), config: dir, runtime: None })
+}
+
+pub fn app_dirs() -> Result<AppDirs> {
+    resolve_app_dirs(location(), env::var_os(HOME_ENV).map(PathBuf::from), &system_dirs())
+}
+
+/// Moves `~/.otp-tool` to the XDG directories when they are used for the
+/// first time. Returns the old and the new data directory when it did.
+pub fn migrate_legacy_dir() -> Result<Option<(PathBuf, PathBuf)>> {
+    if !is_default_location() {
+        return Ok(None);
+    }
+    let system = system_dirs();
+    let legacy = legacy_dir(&system)?;
+    let dirs = resolve_app_dirs(&Location::default(), None, &system)?;
+    Ok(migrate_dir(&legacy, &dirs)?.then_some((legacy, dirs.data)))
+}
+
+fn migrate_dir(legacy: &Path, dirs: &AppDirs) -> Result<bool> {
+    if dirs.data == legacy || !legacy.is_dir() || !is_missing_or_empty(&dirs.data)? {
+        return Ok(false);
+    }
+    // Another invocation may be moving it at the same time
+    let _lock = lock_in(legacy.to_str().unwrap(), dirs)?;
+    if !legacy.is_dir() || !is_missing_or_empty(&dirs.data)? {
+        return Ok(false);
+    }
+
+    if dirs.data.exists() {
+        std::fs::remove_dir(&dirs.data)?;
+    }
+    if let Some(parent) = dirs.data.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    move_path(legacy, &dirs.data).with_context(
+        || format!("Failed to move {} to {}, move it by hand", legacy.display(), dirs.data.display())
+    )?;
+
+    // The config of every profile moves on to the config directory
+    let config_name = format!("{}.yaml", APP_NAME);
+    let mut moves = vec![(dirs.data.join(&config_name), dirs.config.join(&config_name))];
+    let profiles_dir = dirs.data.join("profiles");
+    if profiles_dir.is_dir() {
+        for entry in std::fs::read_dir(&profiles_dir)? {
+            let profile = entry?.file_name();
+            moves.push((
+                profiles_dir.join(&profile).join(&config_name),
+                dirs.config.join("profiles").join(&profile).join(&config_name),
+            ));
+        }
+    }
+    for (from, to) in moves.into_iter().filter(|(from, _)| from.is_file()) {
+        create_dir_all(to.parent().unwrap())?;
+        move_path(&from, &to).with_context(
+            || format!("Failed to move {} to {}", from.display(), to.display())
+        )?;
+    }
+    Ok(true)
+}
+
+fn is_missing_or_empty(dir: &Path) -> Result<bool> {
+    match std::fs::read_dir(dir) {
+        Ok(mut entries) => Ok(entries.next().is_none()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(true),
+        Err(e) => Err(e).with_context(|| format!("Failed to read directory: {}", dir.display())),
+    }
+}
+
+/// Renames a file or directory, copying and deleting it instead when `to`
+/// is on another file system.
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            if let Err(e) = copy_recursive(from, to) {
+                // Leave nothing half copied behind, `from` is still complete
+                let _ = if to.is_dir() { std::fs::remove_dir_all(to) } else { std::fs::remove_file(to) };
+                return Err(e.into());
+            }
+            if from.is_dir() { std::fs::remove_dir_all(from)? } else { std::fs::remove_file(from)? }
+            Ok(())
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Copies a file, or a directory with everything in it, keeping permissions.
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    if !from.is_dir() {
+        return std::fs::copy(from, to).map(|_| ());
+    }
+    std::fs::create_dir(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    std::fs::set_permissions(to, std::fs::metadata(from)?.permissions())
+}
+
+/// Only the owner may read and write files or open directories.
+pub const FILE_MODE: u32 = 0o600;
+pub const DIR_MODE: u32 = 0o700;
+
+/// Options for a new file only readable by the owner.
+fn private_file_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    #[cfg(unix)]
+    options.mode(FILE_MODE);
+    options
+}
+
+/// Exclusive lock on a file, released when dropped. It is an OS advisory
+/// lock on the lock file, so the lock of a crashed process is released by
+/// the OS and a leftover lock file does not block anyone.
+pub struct FileLock {
+    path: String,
+    _file: File,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Removed while still locked, see `same_file`
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Whether `path` still names the open `file`. A waiter may lock a lock
+/// file just after its holder removed it, and must then start over.
+#[cfg(unix)]
+fn same_file(file: &File, path: &str) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(open), Ok(named)) => open.dev() == named.dev() && open.ino() == named.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_file: &File, path: &str) -> bool {
+    Path::new(path).exists()
+}
+
+/// Locks of files in the data directory go to the runtime directory when
+/// there is one, named after a hash of the locked path.
+fn lock_path(file_path: &str, dirs: &AppDirs) -> Result<String> {
+    match &dirs.runtime {
+        Some(runtime) if Path::new(file_path).starts_with(&dirs.data) => {
+            create_dir_all(runtime)?;
+            let hash: String = Sha256::digest(file_path.as_bytes())[..16].iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            Ok(runtime.join(format!("{}.lock", hash)).to_str().unwrap().to_string())
+        }
+        _ => Ok(format!("{}.lock", file_path)),
+    }
+}
+
+pub fn lock(file_path: &str) -> Result<FileLock> {
+    lock_in(file_path, &app_dirs()?)
+}
+
+fn lock_in(file_path: &str, dirs: &AppDirs) -> Result<FileLock> {
+    let lock_path = lock_path(file_path, dirs)?;
+    for _ in 0..LOCK_RETRIES {
+        let file = private_file_options().create(true).truncate(false).open(&lock_path).with_context(
+            || format!("Failed to create lock file: {}", lock_path)
+        )?;
+        match file.try_lock() {
+            Ok(()) if same_file(&file, &lock_path) => return Ok(FileLock { path: lock_path, _file: file }),
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => thread::sleep(LOCK_RETRY_DELAY),
+            Err(TryLockError::Error(e)) => return Err(e).with_context(
+                || format!("Failed to lock file: {}", lock_path)
+            ),
+        }
+    }
+    Err(anyhow::anyhow!("Timed out waiting for lock: {}", lock_path))
+}
 
 pub fn read_bin(file_path: &str) -> Result<Vec<u8>> {
     let mut file = File::open(file_path)?;
@@ -11,14 +282,91 @@ pub fn read_bin(file_path: &str) -> Result<Vec<u8>> {
     Ok(contents)
 }
 
-pub fn write_bin(file_path: &str, contents: &Vec<u8>) -> Result<()> {
-    let mut file = File::create(file_path)?;
-    file.write_all(contents).with_context(
-        || format!("Failed to write to file: {}", file_path)
+/// Temporary file next to `file_path` to write to before `replace_with`.
+pub fn temp_path(file_path: &str) -> String {
+    format!("{}.{}.tmp", file_path, std::process::id())
+}
+
+/// Writes through a temporary file in the same directory that is renamed
+/// over the target, so a crash leaves either the old or the new contents
+/// and never a truncated file. The file is only readable by the owner.
+pub fn write_bin(file_path: &str, contents: &[u8]) -> Result<()> {
+    let temp_path = temp_path(file_path);
+    let mut file = private_file_options().create(true).truncate(true).open(&temp_path).with_context(
+        || format!("Failed to create file: {}", temp_path)
     )?;
+    if let Err(e) = file.write_all(contents) {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e).with_context(|| format!("Failed to write to file: {}", file_path));
+    }
+    drop(file);
+    replace_with(&temp_path, file_path)
+}
+
+/// Restricts, fsyncs and renames a fully written `temp_path` over `file_path`.
+pub fn replace_with(temp_path: &str, file_path: &str) -> Result<()> {
+    let result = sync_private(temp_path).and_then(|_| std::fs::rename(temp_path, file_path).with_context(
+        || format!("Failed to replace file: {}", file_path)
+    ));
+    if result.is_err() {
+        let _ = std::fs::remove_file(temp_path);
+        return result;
+    }
+
+    // Persist the rename itself, best effort as not every platform allows it
+    if let Some(dir) = Path::new(file_path).parent().and_then(|dir| File::open(dir).ok()) {
+        let _ = dir.sync_all();
+    }
     Ok(())
 }
 
+fn sync_private(file_path: &str) -> Result<()> {
+    let file = OpenOptions::new().write(true).open(file_path)?;
+    // The file may have been created by someone else, e.g. confy
+    #[cfg(unix)]
+    file.set_permissions(std::fs::Permissions::from_mode(FILE_MODE))?;
+    file.sync_all().with_context(|| format!("Failed to sync file: {}", file_path))
+}
+
+/// Creates a directory and its parents, only accessible by the owner.
+pub fn create_dir_all(dir_path: &Path) -> Result<()> {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(DIR_MODE);
+    builder.create(dir_path).with_context(
+        || format!("Failed to create directory: {}", dir_path.display())
+    )
+}
+
+/// Files and directories below `dir_path`, itself included, that other
+/// users can access, with their current mode.
+#[cfg(unix)]
+pub fn loose_permissions(dir_path: &str) -> Result<Vec<(PathBuf, u32)>> {
+    fn walk(path: &Path, loose: &mut Vec<(PathBuf, u32)>) -> Result<()> {
+        let metadata = std::fs::symlink_metadata(path)?;
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 && !metadata.file_type().is_symlink() {
+            loose.push((path.to_path_buf(), mode));
+        }
+        if metadata.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                walk(&entry?.path(), loose)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut loose = Vec::new();
+    walk(Path::new(dir_path), &mut loose)?;
+    Ok(loose)
+}
+
+#[cfg(not(unix))]
+pub fn loose_permissions(_dir_path: &str) -> Result<Vec<(PathBuf, u32)>> {
+    Ok(Vec::new())
+}
+
 pub fn delete_file(file_path: &str) -> Result<()> {
     std::fs::remove_file(file_path).with_context(
         || format!("Failed to delete file: {}", file_path)
@@ -41,23 +389,47 @@ pub fn list_files_in_dir(dir_path: &str) -> Result<Vec<String>> {
     Ok(files)
 }
 
-pub fn get_app_data_dir() -> Result<String> {
-    let home_dir = env::home_dir().with_context(
-        || "Failed to get home directory"
-    )?;
+/// Paths of all files below `dir_path`, relative to it.
+pub fn list_files_recursive(dir_path: &str) -> Result<Vec<PathBuf>> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        let relative_dir = dir.strip_prefix(root)?;
+        for file_name in list_files_in_dir(dir.to_str().unwrap())? {
+            files.push(relative_dir.join(file_name));
+        }
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                walk(root, &entry.path(), files)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(Path::new(dir_path), Path::new(dir_path), &mut files)?;
+    Ok(files)
+}
 
-    let app_data_dir = home_dir.join(format!(".{}", APP_NAME));
+pub fn get_app_data_dir() -> Result<String> {
+    let app_data_dir = app_dirs()?.data;
     if !app_data_dir.exists() {
-        std::fs::create_dir_all(&app_data_dir)?;
+        create_dir_all(&app_data_dir)?;
     }
     Ok(app_data_dir.to_str().unwrap().to_string())
 }
 
+pub fn get_config_dir() -> Result<String> {
+    let config_dir = app_dirs()?.config;
+    if !config_dir.exists() {
+        create_dir_all(&config_dir)?;
+    }
+    Ok(config_dir.to_str().unwrap().to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
-    use std::path::PathBuf;
 
     fn create_test_file(dir: &TempDir, name: &str, content: &[u8]) -> PathBuf {
         let file_path = dir.path().join(name);
@@ -90,10 +462,59 @@ mod tests {
 
     #[test]
     fn test_write_to_nonexistent_directory() {
-        let result = write_bin("/nonexistent/directory/file.bin", &vec![1, 2, 3]);
+        let result = write_bin("/nonexistent/directory/file.bin", &[1, 2, 3]);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_write_bin_replaces_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "vault.bin", b"old contents");
+        let file_path_str = file_path.to_str().unwrap();
+
+        write_bin(file_path_str, b"new").unwrap();
+
+        assert_eq!(read_bin(file_path_str).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_private_permissions() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("app/secrets");
+        create_dir_all(&dir).unwrap();
+        let file_path = dir.join("github.bin");
+        // Replacing a readable file must not keep its mode
+        std::fs::write(&file_path, b"old").unwrap();
+        std::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_bin(file_path.to_str().unwrap(), b"secret").unwrap();
+
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&file_path), FILE_MODE);
+        assert_eq!(mode(&dir), DIR_MODE);
+        assert_eq!(mode(&temp_dir.path().join("app")), DIR_MODE);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_loose_permissions() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path().join("app");
+        create_dir_all(&app_dir.join("secrets")).unwrap();
+        write_bin(app_dir.join("secrets/private.bin").to_str().unwrap(), b"").unwrap();
+        assert!(loose_permissions(app_dir.to_str().unwrap()).unwrap().is_empty());
+
+        let readable = app_dir.join("secrets/readable.bin");
+        std::fs::write(&readable, b"").unwrap();
+        std::fs::set_permissions(&readable, std::fs::Permissions::from_mode(0o644)).unwrap();
+        std::fs::set_permissions(&app_dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut loose = loose_permissions(app_dir.to_str().unwrap()).unwrap();
+        loose.sort();
+        assert_eq!(loose, [(app_dir.clone(), 0o755), (readable, 0o644)]);
+    }
+
     #[test]
     fn test_delete_file_success() {
         let temp_dir = TempDir::new().unwrap();
@@ -160,6 +581,19 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_list_files_recursive() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_file(&temp_dir, "top.bin", b"");
+        std::fs::create_dir_all(temp_dir.path().join("work/aws")).unwrap();
+        create_test_file(&temp_dir, "work/aws/prod.bin", b"");
+        std::fs::create_dir(temp_dir.path().join("empty")).unwrap();
+
+        let mut files = list_files_recursive(temp_dir.path().to_str().unwrap()).unwrap();
+        files.sort();
+        assert_eq!(files, [PathBuf::from("top.bin"), ["work", "aws", "prod.bin"].iter().collect()]);
+    }
+
     #[test]
     fn test_get_app_data_dir() {
         let app_dir = get_app_data_dir().unwrap();
@@ -171,6 +605,170 @@ mod tests {
         assert!(std::path::Path::new(&app_dir).exists());
     }
 
+    fn xdg_system_dirs() -> SystemDirs {
+        SystemDirs {
+            home: Some("/home/alice".into()),
+            data: Some("/home/alice/.local/share".into()),
+            config: Some("/home/alice/.config".into()),
+            runtime: Some("/run/user/1000".into()),
+        }
+    }
+
+    #[test]
+    fn test_resolve_app_dirs_xdg() {
+        let system = xdg_system_dirs();
+        let dirs = resolve_app_dirs(&Location::default(), None, &system).unwrap();
+        assert_eq!(dirs, AppDirs {
+            data: "/home/alice/.local/share/otp-tool".into(),
+            config: "/home/alice/.config/otp-tool".into(),
+            runtime: Some("/run/user/1000/otp-tool".into()),
+        });
+
+        let work = Location { vault: None, profile: Some("work".to_string()) };
+        let dirs = resolve_app_dirs(&work, Some(PathBuf::new()), &system).unwrap();
+        assert_eq!(dirs.data, PathBuf::from("/home/alice/.local/share/otp-tool/profiles/work"));
+        assert_eq!(dirs.config, PathBuf::from("/home/alice/.config/otp-tool/profiles/work"));
+    }
+
+    #[test]
+    fn test_resolve_app_dirs_single_dir() {
+        let system = xdg_system_dirs();
+        let custom = Location { vault: Some("/tmp/vault".into()), profile: None };
+        let single = |data: &str| AppDirs { data: data.into(), config: data.into(), runtime: None };
+
+        assert_eq!(resolve_app_dirs(&custom, Some("/srv/otp".into()), &system).unwrap(), single("/tmp/vault"));
+        assert_eq!(resolve_app_dirs(&Location::default(), Some("/srv/otp".into()), &system).unwrap(), single("/srv/otp"));
+
+        // Without XDG directories, as on other platforms
+        let system = SystemDirs { home: Some("/home/alice".into()), ..SystemDirs::default() };
+        assert_eq!(resolve_app_dirs(&Location::default(), None, &system).unwrap(), single("/home/alice/.otp-tool"));
+        assert!(resolve_app_dirs(&Location::default(), None, &SystemDirs::default()).is_err());
+    }
+
+    #[test]
+    fn test_migrate_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let legacy = temp_dir.path().join(".otp-tool");
+        std::fs::create_dir_all(legacy.join("secrets")).unwrap();
+        std::fs::create_dir_all(legacy.join("profiles/work/secrets")).unwrap();
+        std::fs::write(legacy.join("secrets/github.bin"), b"entry").unwrap();
+        std::fs::write(legacy.join("otp-tool.yaml"), b"digits: 6").unwrap();
+        std::fs::write(legacy.join("profiles/work/otp-tool.yaml"), b"digits: 8").unwrap();
+
+        let dirs = AppDirs {
+            data: temp_dir.path().join("share/otp-tool"),
+            config: temp_dir.path().join("config/otp-tool"),
+            runtime: None,
+        };
+        assert!(migrate_dir(&legacy, &dirs).unwrap());
+
+        assert!(!legacy.exists());
+        assert_eq!(std::fs::read(dirs.data.join("secrets/github.bin")).unwrap(), b"entry");
+        assert!(dirs.data.join("profiles/work/secrets").is_dir());
+        assert_eq!(std::fs::read(dirs.config.join("otp-tool.yaml")).unwrap(), b"digits: 6");
+        assert_eq!(std::fs::read(dirs.config.join("profiles/work/otp-tool.yaml")).unwrap(), b"digits: 8");
+        assert!(!dirs.data.join("otp-tool.yaml").exists());
+
+        assert!(!temp_dir.path().join(".otp-tool.lock").exists());
+
+        // Only once
+        std::fs::create_dir(&legacy).unwrap();
+        assert!(!migrate_dir(&legacy, &dirs).unwrap());
+        assert!(legacy.exists());
+    }
+
+    #[test]
+    fn test_migrate_dir_into_empty_data_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let legacy = temp_dir.path().join(".otp-tool");
+        std::fs::create_dir_all(legacy.join("secrets")).unwrap();
+        std::fs::write(legacy.join("secrets/github.bin"), b"entry").unwrap();
+        let dirs = AppDirs {
+            data: temp_dir.path().join("share/otp-tool"),
+            config: temp_dir.path().join("config/otp-tool"),
+            runtime: None,
+        };
+
+        // A data directory with files in it is never overwritten
+        std::fs::create_dir_all(&dirs.data).unwrap();
+        std::fs::write(dirs.data.join("vault.salt"), b"salt").unwrap();
+        assert!(!migrate_dir(&legacy, &dirs).unwrap());
+        assert!(legacy.join("secrets/github.bin").exists());
+
+        std::fs::remove_file(dirs.data.join("vault.salt")).unwrap();
+        assert!(migrate_dir(&legacy, &dirs).unwrap());
+        assert!(!legacy.exists());
+        assert_eq!(std::fs::read(dirs.data.join("secrets/github.bin")).unwrap(), b"entry");
+    }
+
+    #[test]
+    fn test_copy_recursive() {
+        let temp_dir = TempDir::new().unwrap();
+        let from = temp_dir.path().join("from");
+        std::fs::create_dir_all(from.join("secrets/work")).unwrap();
+        std::fs::write(from.join("secrets/work/aws.bin"), b"entry").unwrap();
+        std::fs::write(from.join("vault.salt"), b"salt").unwrap();
+
+        let to = temp_dir.path().join("to");
+        copy_recursive(&from, &to).unwrap();
+        assert_eq!(std::fs::read(to.join("secrets/work/aws.bin")).unwrap(), b"entry");
+        assert_eq!(std::fs::read(to.join("vault.salt")).unwrap(), b"salt");
+        assert!(from.join("vault.salt").exists());
+        assert!(copy_recursive(&from, &to).is_err());
+    }
+
+    #[test]
+    fn test_lock_path() {
+        let dirs = |runtime: Option<PathBuf>| AppDirs { data: "/data/otp-tool".into(), config: "/config".into(), runtime };
+        assert_eq!(lock_path("/data/otp-tool/vault.bin", &dirs(None)).unwrap(), "/data/otp-tool/vault.bin.lock");
+        assert_eq!(lock_path("/elsewhere/vault.bin", &dirs(Some("/unused".into()))).unwrap(), "/elsewhere/vault.bin.lock");
+
+        let temp_dir = TempDir::new().unwrap();
+        let runtime = temp_dir.path().join("otp-tool");
+        let path = lock_path("/data/otp-tool/vault.bin", &dirs(Some(runtime.clone()))).unwrap();
+        assert!(Path::new(&path).starts_with(&runtime));
+        assert!(path.ends_with(".lock"));
+        assert_ne!(path, lock_path("/data/otp-tool/secrets/github.bin", &dirs(Some(runtime))).unwrap());
+    }
+
+    #[test]
+    fn test_lock_is_exclusive_and_released_on_drop() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("locked.bin");
+        let file_path_str = file_path.to_str().unwrap();
+        let lock_path = temp_dir.path().join("locked.bin.lock");
+
+        let guard = lock(file_path_str).unwrap();
+        assert!(lock_path.exists());
+
+        drop(guard);
+        assert!(!lock_path.exists());
+        assert!(lock(file_path_str).is_ok());
+    }
+
+    #[test]
+    fn test_lock_ignores_leftover_lock_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("locked.bin");
+        let file_path_str = file_path.to_str().unwrap();
+
+        // Left behind by a process that crashed while holding the lock
+        std::fs::write(temp_dir.path().join("locked.bin.lock"), b"").unwrap();
+        let start = std::time::Instant::now();
+        assert!(lock(file_path_str).is_ok());
+        assert!(start.elapsed() < LOCK_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_lock_held_by_another_handle() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join("locked.bin.lock");
+
+        let _guard = lock(temp_dir.path().join("locked.bin").to_str().unwrap()).unwrap();
+        let other = File::open(&lock_path).unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+    }
+
     #[test]
     fn test_write_read_empty_file() {
         let temp_dir = TempDir::new().unwrap();
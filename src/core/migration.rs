@@ -0,0 +1,557 @@
+use anyhow::{Error, Result};
+use base64::Engine;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::core::{base32, config::AppConfig, entry::{Entry, OtpKind}, oathtool::{Algorithm, OtpParams}};
+
+/// Scheme of the "Transfer accounts" export of Google Authenticator.
+pub const SCHEME: &str = "otpauth-migration://";
+
+/// Accounts per exported URI. Google Authenticator itself puts about ten
+/// in each code, more make the QR code too dense to scan reliably.
+pub const EXPORT_BATCH_SIZE: usize = 10;
+
+/// Upper bound for the number of parts of a batch, far above what the app
+/// produces, so a corrupt payload cannot claim billions of parts.
+const MAX_BATCH_SIZE: u64 = 1000;
+
+/// Minimal reader of the protobuf wire format, enough for the
+/// `MigrationPayload` message.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.bytes.split_first()
+                .ok_or_else(|| Error::msg("Truncated migration payload"))?;
+            self.bytes = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::msg("Invalid varint in migration payload"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.bytes.len() {
+            return Err(Error::msg("Truncated migration payload"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    /// Next field number and value, `None` at the end of the message.
+    fn field(&mut self) -> Result<Option<(u64, Value<'a>)>> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed
+            }
+            wire_type => return Err(Error::msg(format!("Unsupported wire type {} in migration payload", wire_type))),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+/// Writer of the protobuf wire format, the counterpart of `Reader`.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn varint_field(&mut self, number: u64, value: u64) {
+        self.varint(number << 3);
+        self.varint(value);
+    }
+
+    fn bytes_field(&mut self, number: u64, bytes: &[u8]) {
+        self.varint(number << 3 | 2);
+        self.varint(bytes.len() as u64);
+        self.bytes.extend_from_slice(bytes);
+    }
+}
+
+fn text(bytes: &[u8]) -> Result<String> {
+    Ok(String::from_utf8(bytes.to_vec())?.trim().to_string())
+}
+
+/// Decodes the base64 `data` parameter of an `otpauth-migration://` URI.
+fn payload_bytes(uri: &str) -> Result<Vec<u8>> {
+    let uri = uri.trim();
+    let rest = uri.get(..SCHEME.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+        .map(|_| &uri[SCHEME.len()..])
+        .ok_or_else(|| Error::msg("Not an otpauth-migration:// URI"))?;
+    let (_, query) = rest.split_once('?').ok_or_else(|| Error::msg("Missing data in migration URI"))?;
+    let data = query.split('&')
+        .find_map(|pair| pair.strip_prefix("data="))
+        .ok_or_else(|| Error::msg("Missing data in migration URI"))?;
+    // `+` is part of the base64 alphabet here, not an encoded space
+    let data = percent_decode_str(data).decode_utf8()?;
+    base64::engine::general_purpose::STANDARD_PAD_INDIFFERENT
+        .decode(data.trim())
+        .map_err(|e| Error::msg(format!("Invalid data in migration URI: {}", e)))
+}
+
+/// One account of a migration payload, as found in the `OtpParameters`
+/// message.
+#[derive(Debug, Default)]
+struct OtpParameters {
+    secret: Vec<u8>,
+    name: String,
+    issuer: String,
+    algorithm: u64,
+    digits: u64,
+    otp_type: u64,
+    counter: u64,
+}
+
+impl OtpParameters {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut parameters = OtpParameters::default();
+        let mut reader = Reader::new(bytes);
+        while let Some((number, value)) = reader.field()? {
+            match (number, value) {
+                (1, Value::Bytes(bytes)) => parameters.secret = bytes.to_vec(),
+                (2, Value::Bytes(bytes)) => parameters.name = text(bytes)?,
+                (3, Value::Bytes(bytes)) => parameters.issuer = text(bytes)?,
+                (4, Value::Varint(value)) => parameters.algorithm = value,
+                (5, Value::Varint(value)) => parameters.digits = value,
+                (6, Value::Varint(value)) => parameters.otp_type = value,
+                (7, Value::Varint(value)) => parameters.counter = value,
+                _ => {}
+            }
+        }
+        Ok(parameters)
+    }
+
+    /// Issuer and account, the name being usually the label of the
+    /// original URI, `issuer:account`.
+    fn label(&self) -> (Option<String>, Option<String>) {
+        let (issuer, account) = match self.name.split_once(':') {
+            Some((prefix, account)) if self.issuer.is_empty() || prefix.trim() == self.issuer => {
+                (prefix.trim(), account.trim())
+            }
+            _ => (self.issuer.as_str(), self.name.as_str()),
+        };
+        let some = |value: &str| Some(value.to_string()).filter(|value| !value.is_empty());
+        (some(issuer), some(account))
+    }
+
+    /// Name shown when the account is skipped.
+    fn display_name(&self) -> String {
+        match self.label() {
+            (Some(issuer), Some(account)) => format!("{}:{}", issuer, account),
+            (Some(value), None) | (None, Some(value)) => value,
+            (None, None) => "(unnamed)".to_string(),
+        }
+    }
+
+    fn to_entry(&self) -> Result<Entry> {
+        if self.secret.is_empty() {
+            return Err(Error::msg("missing secret"));
+        }
+        let algorithm = match self.algorithm {
+            // Unspecified is taken as SHA1, like in the app
+            0 | 1 => None,
+            2 => Some(Algorithm::Sha256),
+            3 => Some(Algorithm::Sha512),
+            4 => return Err(Error::msg("MD5 is not supported")),
+            other => return Err(Error::msg(format!("unknown algorithm {}", other))),
+        };
+        let digits = match self.digits {
+            0 | 1 => 6,
+            2 => 8,
+            other => return Err(Error::msg(format!("unknown digit count {}", other))),
+        };
+
+        let secret = base32::encode(&self.secret);
+        let mut entry = match self.otp_type {
+            0 | 2 => {
+                let mut entry = Entry::totp(&secret);
+                entry.period = Some(OtpParams::default().period);
+                entry
+            }
+            1 => Entry::hotp(&secret, self.counter),
+            other => return Err(Error::msg(format!("unknown OTP type {}", other))),
+        };
+        entry.digits = Some(digits);
+        entry.algorithm = algorithm;
+        (entry.issuer, entry.account) = self.label();
+        Ok(entry)
+    }
+}
+
+/// Account of a payload that could not be mapped onto an entry.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Skipped {
+    pub name: String,
+    pub reason: String,
+}
+
+/// Contents of one `otpauth-migration://` URI. A large export is split
+/// into a batch of several URIs sharing the same `batch_id`.
+#[derive(Debug, Default)]
+pub struct Payload {
+    pub entries: Vec<Entry>,
+    pub skipped: Vec<Skipped>,
+    pub batch_size: u64,
+    pub batch_index: u64,
+    pub batch_id: u64,
+}
+
+/// Parses an `otpauth-migration://offline?data=...` URI. Accounts that
+/// otp-tool cannot represent are listed in `skipped` instead of failing the
+/// whole payload.
+pub fn parse(uri: &str) -> Result<Payload> {
+    let bytes = payload_bytes(uri)?;
+    let mut payload = Payload { batch_size: 1, ..Default::default() };
+    let mut reader = Reader::new(&bytes);
+    while let Some((number, value)) = reader.field()? {
+        match (number, value) {
+            (1, Value::Bytes(bytes)) => {
+                let parameters = OtpParameters::decode(bytes)?;
+                match parameters.to_entry() {
+                    Ok(entry) => payload.entries.push(entry),
+                    Err(e) => payload.skipped.push(Skipped { name: parameters.display_name(), reason: e.to_string() }),
+                }
+            }
+            (3, Value::Varint(value)) => payload.batch_size = value.max(1),
+            (4, Value::Varint(value)) => payload.batch_index = value,
+            (5, Value::Varint(value)) => payload.batch_id = value,
+            _ => {}
+        }
+    }
+    if payload.batch_size > MAX_BATCH_SIZE || payload.batch_index >= payload.batch_size {
+        return Err(Error::msg(format!(
+            "Invalid batch {} of {} in migration payload", payload.batch_index + 1, payload.batch_size
+        )));
+    }
+    Ok(payload)
+}
+
+/// `OtpParameters` message for an entry, or why the app cannot hold it.
+fn encode_otp_parameters(service_name: &str, entry: &Entry, config: &AppConfig) -> Result<Vec<u8>> {
+    let params = entry.params(config);
+    let (otp_type, counter) = match entry.kind {
+        OtpKind::Totp if params.period != OtpParams::default().period => {
+            return Err(Error::msg(format!("period of {} seconds is not supported", params.period)));
+        }
+        OtpKind::Totp if params.t0 != 0 => return Err(Error::msg("T0 other than 0 is not supported")),
+        OtpKind::Totp => (2, None),
+        OtpKind::Hotp { counter } => (1, Some(counter)),
+    };
+    let digits = match params.digits {
+        6 => 1,
+        8 => 2,
+        other => return Err(Error::msg(format!("{} digits are not supported", other))),
+    };
+    let algorithm = match params.algorithm {
+        Algorithm::Sha1 => 1,
+        Algorithm::Sha256 => 2,
+        Algorithm::Sha512 => 3,
+    };
+
+    let mut writer = Writer::default();
+    writer.bytes_field(1, &base32::decode(&entry.secret)?);
+    writer.bytes_field(2, entry.account.as_deref().unwrap_or(service_name).as_bytes());
+    if let Some(issuer) = &entry.issuer {
+        writer.bytes_field(3, issuer.as_bytes());
+    }
+    writer.varint_field(4, algorithm);
+    writer.varint_field(5, digits);
+    writer.varint_field(6, otp_type);
+    if let Some(counter) = counter {
+        writer.varint_field(7, counter);
+    }
+    Ok(writer.bytes)
+}
+
+/// Builds the `otpauth-migration://` URIs of a Google Authenticator
+/// transfer, `EXPORT_BATCH_SIZE` entries each, all tagged with `batch_id`.
+/// Entries the app cannot hold are returned as skipped.
+pub fn export(entries: &[(String, Entry)], config: &AppConfig, batch_id: u64) -> (Vec<String>, Vec<Skipped>) {
+    let mut encoded = Vec::new();
+    let mut skipped = Vec::new();
+    for (name, entry) in entries {
+        match encode_otp_parameters(name, entry, config) {
+            Ok(bytes) => encoded.push(bytes),
+            Err(e) => skipped.push(Skipped { name: name.clone(), reason: e.to_string() }),
+        }
+    }
+
+    let batch_size = encoded.len().div_ceil(EXPORT_BATCH_SIZE) as u64;
+    let uris = encoded.chunks(EXPORT_BATCH_SIZE)
+        .enumerate()
+        .map(|(batch_index, chunk)| {
+            let mut writer = Writer::default();
+            for parameters in chunk {
+                writer.bytes_field(1, parameters);
+            }
+            writer.varint_field(2, 1);
+            writer.varint_field(3, batch_size);
+            writer.varint_field(4, batch_index as u64);
+            writer.varint_field(5, batch_id);
+            let data = base64::engine::general_purpose::STANDARD.encode(&writer.bytes);
+            format!("{}offline?data={}", SCHEME, utf8_percent_encode(&data, NON_ALPHANUMERIC))
+        })
+        .collect();
+    (uris, skipped)
+}
+
+/// Keeps track of the parts seen of each batch, to tell which are missing.
+#[derive(Debug, Default)]
+pub struct Batches {
+    seen: BTreeMap<u64, (u64, BTreeSet<u64>)>,
+}
+
+impl Batches {
+    pub fn add(&mut self, payload: &Payload) {
+        let (size, indexes) = self.seen.entry(payload.batch_id).or_default();
+        *size = payload.batch_size;
+        indexes.insert(payload.batch_index);
+    }
+
+    /// Indexes, starting at 0, of the missing parts of each incomplete
+    /// batch, with the size of the batch.
+    pub fn missing(&self) -> Vec<(u64, Vec<u64>)> {
+        self.seen.values()
+            .map(|(size, indexes)| (*size, (0..*size).filter(|i| !indexes.contains(i)).collect::<Vec<_>>()))
+            .filter(|(_, missing)| !missing.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::entry::OtpKind;
+
+    fn field(number: u8, bytes: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![number << 3 | 2, bytes.len() as u8];
+        encoded.extend_from_slice(bytes);
+        encoded
+    }
+
+    fn otp_parameters(secret: &[u8], name: &str, issuer: &str) -> Vec<u8> {
+        let mut bytes = field(1, secret);
+        bytes.extend(field(2, name.as_bytes()));
+        bytes.extend(field(3, issuer.as_bytes()));
+        // SHA1, six digits, TOTP
+        bytes.extend([4 << 3, 1, 5 << 3, 1, 6 << 3, 2]);
+        bytes
+    }
+
+    fn uri(payload: &[u8]) -> String {
+        let data = base64::engine::general_purpose::STANDARD.encode(payload);
+        format!("otpauth-migration://offline?data={}", percent_encoding::utf8_percent_encode(&data, percent_encoding::NON_ALPHANUMERIC))
+    }
+
+    #[test]
+    fn test_parse_migration_uri() {
+        let mut payload = field(1, &otp_parameters(b"Hello!\xde\xad\xbe\xef", "Example:alice@example.com", "Example"));
+        payload.extend(field(1, &otp_parameters(b"12345678901234567890", "bob", "")));
+        // version, batch size, batch index
+        payload.extend([2 << 3, 1, 3 << 3, 1, 4 << 3, 0]);
+
+        let entries = parse(&uri(&payload)).unwrap().entries;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(entries[0].kind, OtpKind::Totp);
+        assert_eq!(entries[0].issuer.as_deref(), Some("Example"));
+        assert_eq!(entries[0].account.as_deref(), Some("alice@example.com"));
+        assert_eq!(entries[1].secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(entries[1].issuer, None);
+        assert_eq!(entries[1].account.as_deref(), Some("bob"));
+    }
+
+    #[test]
+    fn test_parse_issuer_from_name() {
+        let payload = field(1, &otp_parameters(b"12345", "Example: alice", ""));
+        let entries = parse(&uri(&payload)).unwrap().entries;
+        assert_eq!(entries[0].issuer.as_deref(), Some("Example"));
+        assert_eq!(entries[0].account.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn test_parse_unencoded_data() {
+        // `+` and `/` left unescaped by some tools
+        let payload = field(1, &otp_parameters(&[0xfb, 0xff, 0xbf], "alice", ""));
+        let data = base64::engine::general_purpose::STANDARD.encode(&payload);
+        assert!(data.contains('+') || data.contains('/'));
+        let entries = parse(&format!("otpauth-migration://offline?data={}", data)).unwrap().entries;
+        assert_eq!(entries[0].secret, base32::encode(&[0xfb, 0xff, 0xbf]));
+    }
+
+    #[test]
+    fn test_parse_invalid_migration_uri() {
+        assert!(parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP").is_err());
+        assert!(parse("otpauth-migration://offline").is_err());
+        assert!(parse("otpauth-migration://offline?data=!!!").is_err());
+        // Field with a length past the end of the payload
+        assert!(parse(&uri(&[1 << 3 | 2, 10, 1])).is_err());
+        // Batch index past the batch size
+        assert!(parse(&uri(&[3 << 3, 2, 4 << 3, 2])).is_err());
+        assert!(parse(&uri(&[3 << 3, 0xff, 0xff, 0x03])).is_err());
+    }
+
+    #[test]
+    fn test_parse_parameters() {
+        let mut hotp = field(1, b"12345678901234567890");
+        hotp.extend(field(2, b"Example:alice"));
+        // SHA512, eight digits, HOTP, counter 300
+        hotp.extend([4 << 3, 3, 5 << 3, 2, 6 << 3, 1, 7 << 3, 0xac, 0x02]);
+        let mut sha256 = field(1, b"12345678901234567890");
+        sha256.extend(field(2, b"bob"));
+        sha256.extend([4 << 3, 2]);
+        let mut payload = field(1, &hotp);
+        payload.extend(field(1, &sha256));
+
+        let entries = parse(&uri(&payload)).unwrap().entries;
+        assert_eq!(entries[0].kind, OtpKind::Hotp { counter: 300 });
+        assert_eq!(entries[0].algorithm, Some(Algorithm::Sha512));
+        assert_eq!(entries[0].digits, Some(8));
+        assert_eq!(entries[0].period, None);
+        assert_eq!(entries[0].issuer.as_deref(), Some("Example"));
+        assert_eq!(entries[1].kind, OtpKind::Totp);
+        assert_eq!(entries[1].algorithm, Some(Algorithm::Sha256));
+        assert_eq!(entries[1].digits, Some(6));
+        assert_eq!(entries[1].period, Some(30));
+    }
+
+    #[test]
+    fn test_parse_skips_unsupported_entries() {
+        let mut md5 = otp_parameters(b"12345", "Example:alice", "Example");
+        md5.extend([4 << 3, 4]);
+        let mut payload = field(1, &md5);
+        payload.extend(field(1, &field(2, b"bob")));
+        payload.extend(field(1, &otp_parameters(b"12345", "carol", "")));
+
+        let payload = parse(&uri(&payload)).unwrap();
+        assert_eq!(payload.entries.len(), 1);
+        assert_eq!(payload.entries[0].account.as_deref(), Some("carol"));
+        assert_eq!(payload.skipped, vec![
+            Skipped { name: "Example:alice".to_string(), reason: "MD5 is not supported".to_string() },
+            Skipped { name: "bob".to_string(), reason: "missing secret".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_batches() {
+        let part = |index: u8, id: u8| parse(&uri(&[3 << 3, 3, 4 << 3, index, 5 << 3, id])).unwrap();
+        let first = part(0, 7);
+        assert_eq!((first.batch_size, first.batch_index, first.batch_id), (3, 0, 7));
+
+        let mut batches = Batches::default();
+        batches.add(&first);
+        batches.add(&part(2, 7));
+        batches.add(&part(1, 8));
+        assert_eq!(batches.missing(), vec![(3, vec![1]), (3, vec![0, 2])]);
+
+        batches.add(&part(1, 7));
+        assert_eq!(batches.missing(), vec![(3, vec![0, 2])]);
+
+        // A payload without batch fields is a batch of one
+        let single = parse("otpauth-migration://offline?data=").unwrap();
+        assert_eq!((single.batch_size, single.batch_index), (1, 0));
+    }
+
+    #[test]
+    fn test_export_roundtrip() {
+        let mut totp = Entry::totp("JBSWY3DPEHPK3PXP");
+        totp.issuer = Some("Example".to_string());
+        totp.account = Some("alice@example.com".to_string());
+        totp.algorithm = Some(Algorithm::Sha256);
+        let mut hotp = Entry::hotp("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", 300);
+        hotp.digits = Some(8);
+        let entries = vec![("work/alice".to_string(), totp), ("bob".to_string(), hotp)];
+
+        let (uris, skipped) = export(&entries, &AppConfig::default(), 42);
+        assert!(skipped.is_empty());
+        assert_eq!(uris.len(), 1);
+
+        let payload = parse(&uris[0]).unwrap();
+        assert_eq!((payload.batch_size, payload.batch_index, payload.batch_id), (1, 0, 42));
+        assert_eq!(payload.entries[0].secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(payload.entries[0].issuer.as_deref(), Some("Example"));
+        assert_eq!(payload.entries[0].account.as_deref(), Some("alice@example.com"));
+        assert_eq!(payload.entries[0].algorithm, Some(Algorithm::Sha256));
+        assert_eq!(payload.entries[0].digits, Some(6));
+        assert_eq!(payload.entries[1].secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(payload.entries[1].kind, OtpKind::Hotp { counter: 300 });
+        assert_eq!(payload.entries[1].account.as_deref(), Some("bob"));
+        assert_eq!(payload.entries[1].digits, Some(8));
+    }
+
+    #[test]
+    fn test_export_batches() {
+        let entries: Vec<(String, Entry)> = (0..23)
+            .map(|i| (format!("entry{}", i), Entry::totp("JBSWY3DPEHPK3PXP")))
+            .collect();
+
+        let (uris, _) = export(&entries, &AppConfig::default(), 7);
+        assert_eq!(uris.len(), 3);
+        let payloads: Vec<Payload> = uris.iter().map(|uri| parse(uri).unwrap()).collect();
+        assert_eq!(payloads.iter().map(|p| p.entries.len()).collect::<Vec<_>>(), vec![10, 10, 3]);
+        assert!(payloads.iter().enumerate().all(|(i, p)| p.batch_index == i as u64 && p.batch_size == 3 && p.batch_id == 7));
+        assert_eq!(payloads[2].entries[2].account.as_deref(), Some("entry22"));
+    }
+
+    #[test]
+    fn test_export_skips_unsupported_entries() {
+        let mut period = Entry::totp("JBSWY3DPEHPK3PXP");
+        period.period = Some(60);
+        let mut digits = Entry::totp("JBSWY3DPEHPK3PXP");
+        digits.digits = Some(7);
+        let entries = vec![
+            ("period".to_string(), period),
+            ("digits".to_string(), digits),
+            ("ok".to_string(), Entry::totp("JBSWY3DPEHPK3PXP")),
+        ];
+
+        let (uris, skipped) = export(&entries, &AppConfig::default(), 1);
+        assert_eq!(parse(&uris[0]).unwrap().entries.len(), 1);
+        assert_eq!(skipped, vec![
+            Skipped { name: "period".to_string(), reason: "period of 60 seconds is not supported".to_string() },
+            Skipped { name: "digits".to_string(), reason: "7 digits are not supported".to_string() },
+        ]);
+    }
+}
@@ -1,5 +1,15 @@
+pub mod base32;
+pub mod clock;
 pub mod config;
 pub mod encryption;
+pub mod entry;
 pub mod file_system;
 pub mod keyring;
-pub mod oathtool;
\ No newline at end of file
+pub mod migration;
+pub mod names;
+pub mod oathtool;
+pub mod qr;
+pub mod secret;
+pub mod store;
+pub mod time_sync;
+pub mod uri;
@@ -0,0 +1,214 @@
+use anyhow::{Error, Result};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::path::{Component, Path, PathBuf};
+
+/// Separates groups in hierarchical names like `work/aws/prod`.
+pub const SEPARATOR: char = '/';
+const EXTENSION: &str = "bin";
+/// Leaves room for the `.bin.lock` suffix within the usual 255 byte limit.
+const MAX_SEGMENT_LEN: usize = 200;
+
+/// Characters that are not portable in file names, plus `%` itself.
+const UNSAFE: &AsciiSet = &CONTROLS
+    .add(b'/').add(b'\\').add(b'%').add(b':').add(b'*')
+    .add(b'?').add(b'"').add(b'<').add(b'>').add(b'|');
+
+/// Checks a service name: non-empty groups separated by `/`, without `.`
+/// or `..` segments and without control characters.
+pub fn validate(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(Error::msg("Service name cannot be empty"));
+    }
+    for segment in name.split(SEPARATOR) {
+        if segment.is_empty() {
+            return Err(Error::msg(format!("Invalid service name '{}': empty group", name)));
+        }
+        if segment == "." || segment == ".." {
+            return Err(Error::msg(format!("Invalid service name '{}': '{}' is not allowed", name, segment)));
+        }
+        if segment.chars().any(char::is_control) {
+            return Err(Error::msg(format!("Invalid service name '{}': control characters are not allowed", name)));
+        }
+        if encode_segment(segment).len() > MAX_SEGMENT_LEN {
+            return Err(Error::msg(format!("Invalid service name '{}': '{}' is too long", name, segment)));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` is inside `group`, given with or without a trailing `/`.
+pub fn in_group(name: &str, group: &str) -> bool {
+    let group = group.trim_end_matches(SEPARATOR);
+    group.is_empty() || name.strip_prefix(group).is_some_and(|rest| rest.starts_with(SEPARATOR))
+}
+
+/// Entries a possibly misspelled or partial name may refer to, from the
+/// first of these that matches anything:
+/// the exact name, the name or names ending in `/<query>` (so `prod` finds
+/// `work/aws/prod`), names containing the query, and names containing its
+/// characters in order (so `gthb` finds `github`). Case is ignored after
+/// the exact match.
+pub fn candidates<'a>(query: &str, names: &'a [String]) -> Vec<&'a str> {
+    if let Some(name) = names.iter().find(|name| *name == query) {
+        return vec![name];
+    }
+
+    let query = query.to_lowercase();
+    let suffix = format!("{}{}", SEPARATOR, query);
+    let matchers: [&dyn Fn(&str) -> bool; 3] = [
+        &|name| name == query || name.ends_with(&suffix),
+        &|name| name.contains(&query),
+        &|name| is_subsequence(&query, name),
+    ];
+    for matches in matchers {
+        let found: Vec<&str> = names.iter()
+            .filter(|name| matches(&name.to_lowercase()))
+            .map(String::as_str)
+            .collect();
+        if !found.is_empty() {
+            return found;
+        }
+    }
+    Vec::new()
+}
+
+fn is_subsequence(query: &str, name: &str) -> bool {
+    let mut name_chars = name.chars();
+    query.chars().all(|c| name_chars.any(|n| n == c))
+}
+
+/// Percent-encodes the characters that are unsafe in file names. A leading
+/// `.` is encoded too so entries never become hidden files.
+fn encode_segment(segment: &str) -> String {
+    let encoded = utf8_percent_encode(segment, UNSAFE).to_string();
+    match encoded.strip_prefix('.') {
+        Some(rest) => format!("%2E{}", rest),
+        None => encoded,
+    }
+}
+
+fn decode_segment(segment: &str) -> Option<String> {
+    percent_decode_str(segment).decode_utf8().ok().map(|s| s.into_owned())
+}
+
+/// Path of an entry file relative to the secrets directory. Each group is
+/// a subdirectory.
+pub fn to_path(name: &str) -> Result<PathBuf> {
+    validate(name)?;
+    let mut segments: Vec<String> = name.split(SEPARATOR).map(encode_segment).collect();
+    if let Some(file_name) = segments.last_mut() {
+        // Not `set_extension`, which would replace the `.2` of `v1.2`
+        file_name.push_str(&format!(".{}", EXTENSION));
+    }
+    Ok(segments.iter().collect())
+}
+
+/// Path an entry had before names were encoded, when it differs from
+/// `to_path`. Entries were then single files named after the service.
+pub fn legacy_path(name: &str) -> Option<PathBuf> {
+    validate(name).ok()?;
+    let path = PathBuf::from(format!("{}.{}", name, EXTENSION));
+    (!name.contains(SEPARATOR) && to_path(name).ok()? != path).then_some(path)
+}
+
+/// Inverse of `to_path`. Returns `None` for files that are not entries.
+pub fn from_path(path: &Path) -> Option<String> {
+    if path.extension()? != EXTENSION {
+        return None;
+    }
+    let path = path.with_extension("");
+    let segments = path
+        .components()
+        .map(|component| match component {
+            Component::Normal(segment) => decode_segment(segment.to_str()?),
+            _ => None,
+        })
+        .collect::<Option<Vec<String>>>()?;
+
+    let name = segments.join(&SEPARATOR.to_string());
+    validate(&name).ok().map(|_| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("github").is_ok());
+        assert!(validate("work/aws/prod").is_ok());
+        assert!(validate("My Bank: savings").is_ok());
+        assert!(validate(".hidden").is_ok());
+
+        assert!(validate("").is_err());
+        assert!(validate("../../.bashrc").is_err());
+        assert!(validate("work/../prod").is_err());
+        assert!(validate("./github").is_err());
+        assert!(validate("/etc/passwd").is_err());
+        assert!(validate("work/").is_err());
+        assert!(validate("work//prod").is_err());
+        assert!(validate("line\nbreak").is_err());
+        assert!(validate(&"a".repeat(MAX_SEGMENT_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_to_path() {
+        assert_eq!(to_path("github").unwrap(), PathBuf::from("github.bin"));
+        assert_eq!(to_path("work/aws/prod").unwrap(), ["work", "aws", "prod.bin"].iter().collect::<PathBuf>());
+        assert_eq!(to_path("a\\b:c%d").unwrap(), PathBuf::from("a%5Cb%3Ac%25d.bin"));
+        assert_eq!(to_path(".hidden").unwrap(), PathBuf::from("%2Ehidden.bin"));
+        assert_eq!(to_path("v1.2").unwrap(), PathBuf::from("v1.2.bin"));
+        assert!(to_path("../../.bashrc").is_err());
+    }
+
+    #[test]
+    fn test_from_path_roundtrip() {
+        for name in ["github", "work/aws/prod", "a\\b:c%d", ".hidden", "v1.2", "Zürich bank", "100%"] {
+            assert_eq!(from_path(&to_path(name).unwrap()).as_deref(), Some(name));
+        }
+    }
+
+    #[test]
+    fn test_from_path_ignores_other_files() {
+        assert_eq!(from_path(Path::new("github.bin.lock")), None);
+        assert_eq!(from_path(Path::new("vault.salt")), None);
+        assert_eq!(from_path(Path::new("notes")), None);
+        assert_eq!(from_path(Path::new("../github.bin")), None);
+    }
+
+    #[test]
+    fn test_legacy_path() {
+        assert_eq!(legacy_path("a\\b:c%d"), Some(PathBuf::from("a\\b:c%d.bin")));
+        assert_eq!(legacy_path(".hidden"), Some(PathBuf::from(".hidden.bin")));
+        assert_eq!(legacy_path("github"), None);
+        assert_eq!(legacy_path("work/a:b"), None);
+        assert_eq!(legacy_path(".."), None);
+        // Listed under the same name, so lookups can fall back to it
+        assert_eq!(from_path(&legacy_path("My Bank: savings").unwrap()).as_deref(), Some("My Bank: savings"));
+    }
+
+    #[test]
+    fn test_in_group() {
+        assert!(in_group("work/aws/prod", "work"));
+        assert!(in_group("work/aws/prod", "work/aws/"));
+        assert!(in_group("github", ""));
+        assert!(!in_group("work", "work"));
+        assert!(!in_group("workshop/x", "work"));
+    }
+
+    #[test]
+    fn test_candidates() {
+        let names: Vec<String> = ["github", "work/aws/prod", "work/aws/staging", "work/GitHub", "home/prod", "production-db"]
+            .iter().map(|name| name.to_string()).collect();
+
+        assert_eq!(candidates("github", &names), ["github"]);
+        assert_eq!(candidates("staging", &names), ["work/aws/staging"]);
+        assert_eq!(candidates("aws/prod", &names), ["work/aws/prod"]);
+        // Leaf matches win over substrings like `production-db`
+        assert_eq!(candidates("prod", &names), ["work/aws/prod", "home/prod"]);
+        assert_eq!(candidates("GITHUB", &names), ["github", "work/GitHub"]);
+        assert_eq!(candidates("stag", &names), ["work/aws/staging"]);
+        assert_eq!(candidates("gthb", &names), ["github", "work/GitHub"]);
+        assert!(candidates("gitlab", &names).is_empty());
+    }
+}
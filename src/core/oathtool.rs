@@ -1,21 +1,193 @@
-use std::process::Command;
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use crate::core::{base32, clock::Clock};
 
-pub fn generate(secret: &str, digits: u8, time: u8) -> String {
-    let output = Command::new("oathtool")
-        .arg(format!("--totp"))
-        .arg(format!("--base32"))
-        .arg(format!("-d {}", digits))
-        .arg(format!("-s {}", time))
-        .arg(secret)
-        .output()
-        .expect("Failed to execute oathtool");
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Algorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// Parameters shared by every code generated for an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OtpParams {
+    pub digits: u32,
+    pub period: u64,
+    pub t0: u64,
+    pub algorithm: Algorithm,
+}
+
+impl Default for OtpParams {
+    fn default() -> Self {
+        OtpParams { digits: 6, period: 30, t0: 0, algorithm: Algorithm::Sha1 }
+    }
+}
+
+fn mac<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = <M as Mac>::new_from_slice(key).map_err(
+        |e| Error::msg(format!("Invalid HMAC key: {}", e))
+    )?;
+    mac.update(message);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn sign(key: &[u8], message: &[u8], algorithm: Algorithm) -> Result<Vec<u8>> {
+    match algorithm {
+        Algorithm::Sha1 => mac::<Hmac<Sha1>>(key, message),
+        Algorithm::Sha256 => mac::<Hmac<Sha256>>(key, message),
+        Algorithm::Sha512 => mac::<Hmac<Sha512>>(key, message),
+    }
+}
+
+/// RFC 4226 HOTP value for `counter`.
+pub fn hotp(key: &[u8], counter: u64, digits: u32, algorithm: Algorithm) -> Result<String> {
+    if !(1..=10).contains(&digits) {
+        return Err(Error::msg(format!("Unsupported number of digits: {}", digits)));
+    }
+
+    let hash = sign(key, &counter.to_be_bytes(), algorithm)?;
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    let code = binary as u64 % 10u64.pow(digits);
 
-    String::from_utf8_lossy(&output.stdout).trim().to_string()
+    Ok(format!("{:0width$}", code, width = digits as usize))
+}
+
+/// RFC 6238 TOTP value at `timestamp` (seconds since the Unix epoch).
+pub fn totp(key: &[u8], timestamp: u64, period: u64, t0: u64, digits: u32, algorithm: Algorithm) -> Result<String> {
+    if period == 0 {
+        return Err(Error::msg("Time step must be greater than zero"));
+    }
+    if timestamp < t0 {
+        return Err(Error::msg(format!("Timestamp {} is before T0 {}", timestamp, t0)));
+    }
+    hotp(key, (timestamp - t0) / period, digits, algorithm)
+}
+
+/// Searches `counter..=counter + look_ahead` for two consecutive codes as
+/// described in RFC 4226 section 7.4, returning the counter of `next_code`.
+pub fn resync(key: &[u8], counter: u64, look_ahead: u64, code: &str, next_code: &str, digits: u32, algorithm: Algorithm) -> Result<Option<u64>> {
+    let mut expected = hotp(key, counter, digits, algorithm)?;
+    for candidate in counter..=counter.saturating_add(look_ahead) {
+        let following = hotp(key, candidate + 1, digits, algorithm)?;
+        if expected == code && following == next_code {
+            return Ok(Some(candidate + 1));
+        }
+        expected = following;
+    }
+    Ok(None)
+}
+
+fn codes_match(expected: &str, actual: &str) -> bool {
+    // Compare without short-circuiting so timing does not leak the code
+    expected.len() == actual.len()
+        && expected.bytes().zip(actual.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Looks for `code` within `skew` time steps of `timestamp`, nearest steps
+/// first. Returns the offset and the absolute time step that matched.
+pub fn verify_totp(secret: &str, params: &OtpParams, timestamp: u64, code: &str, skew: u64) -> Result<Option<(i64, u64)>> {
+    let mut codes = generate_window(secret, params, timestamp, skew)?;
+    codes.sort_by_key(|(offset, _, _)| (offset.abs(), *offset));
+
+    let matched = codes.into_iter().find(|(_, _, expected)| codes_match(expected, code));
+    Ok(matched.map(|(offset, start, _)| (offset, (start - params.t0) / params.period)))
+}
+
+/// Looks for `code` in `counter..=counter + look_ahead`, returning the
+/// counter that matched.
+pub fn verify_hotp(secret: &str, params: &OtpParams, counter: u64, code: &str, look_ahead: u64) -> Result<Option<u64>> {
+    let key = decode_key(secret)?;
+    for candidate in counter..=counter.saturating_add(look_ahead) {
+        if codes_match(&hotp(&key, candidate, params.digits, params.algorithm)?, code) {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
+}
+
+fn decode_key(secret: &str) -> Result<Vec<u8>> {
+    let key = base32::decode(secret)?;
+    if key.is_empty() {
+        return Err(Error::msg("Secret is empty"));
+    }
+    Ok(key)
+}
+
+/// Seconds until the time step containing `timestamp` ends.
+pub fn seconds_remaining(params: &OtpParams, timestamp: u64) -> u64 {
+    if params.period == 0 {
+        return 0;
+    }
+    params.period - timestamp.saturating_sub(params.t0) % params.period
+}
+
+pub fn generate(secret: &str, params: &OtpParams, clock: &dyn Clock) -> Result<String> {
+    generate_at(secret, params, clock.now()?)
+}
+
+pub fn generate_at(secret: &str, params: &OtpParams, timestamp: u64) -> Result<String> {
+    let key = decode_key(secret)?;
+    totp(&key, timestamp, params.period, params.t0, params.digits, params.algorithm)
+}
+
+/// Codes for the `window` time steps before and after the one containing
+/// `timestamp`, as `(offset, step start, code)`.
+pub fn generate_window(secret: &str, params: &OtpParams, timestamp: u64, window: u64) -> Result<Vec<(i64, u64, String)>> {
+    if params.period == 0 {
+        return Err(Error::msg("Time step must be greater than zero"));
+    }
+    let key = decode_key(secret)?;
+    let step_start = timestamp - timestamp.saturating_sub(params.t0) % params.period;
+
+    let mut codes = Vec::new();
+    for offset in -(window as i64)..=window as i64 {
+        let Some(start) = step_start.checked_add_signed(offset * params.period as i64) else {
+            continue;
+        };
+        if start < params.t0 {
+            continue;
+        }
+        let code = totp(&key, start, params.period, params.t0, params.digits, params.algorithm)?;
+        codes.push((offset, start, code));
+    }
+    Ok(codes)
+}
+
+pub fn generate_hotp(secret: &str, counter: u64, params: &OtpParams) -> Result<String> {
+    hotp(&decode_key(secret)?, counter, params.digits, params.algorithm)
+}
+
+pub fn resync_hotp(secret: &str, counter: u64, look_ahead: u64, code: &str, next_code: &str, params: &OtpParams) -> Result<Option<u64>> {
+    resync(&decode_key(secret)?, counter, look_ahead, code, next_code, params.digits, params.algorithm)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::clock::FixedClock;
+
+    const RFC_SHA1_KEY: &[u8] = b"12345678901234567890";
+    const RFC_SHA256_KEY: &[u8] = b"12345678901234567890123456789012";
+    const RFC_SHA512_KEY: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+    const CLOCK: FixedClock = FixedClock(1111111109);
+
+    fn params(digits: u32, period: u64) -> OtpParams {
+        OtpParams { digits, period, ..OtpParams::default() }
+    }
 
     #[test]
     fn test_generate_with_valid_secret() {
@@ -23,9 +195,9 @@ mod tests {
         let secret = "JBSWY3DPEHPK3PXP"; // "Hello World!" in Base32
         let digits = 6;
         let time = 30;
-        
-        let result = generate(secret, digits, time);
-        
+
+        let result = generate(secret, &params(digits, time), &CLOCK).unwrap();
+
         // The result should be a 6-digit numeric string
         assert_eq!(result.len(), 6);
         assert!(result.chars().all(|c| c.is_ascii_digit()));
@@ -36,48 +208,62 @@ mod tests {
         let secret = "JBSWY3DPEHPK3PXP";
         let digits = 8;
         let time = 30;
-        
-        let result = generate(secret, digits, time);
-        
+
+        let result = generate(secret, &params(digits, time), &CLOCK).unwrap();
+
         // The result should be an 8-digit numeric string
         assert_eq!(result.len(), 8);
         assert!(result.chars().all(|c| c.is_ascii_digit()));
     }
 
-    #[test] 
+    #[test]
     fn test_generate_consistent_output() {
         let secret = "JBSWY3DPEHPK3PXP";
         let digits = 6;
         let time = 30;
-        
-        // Generate multiple times in quick succession
-        let result1 = generate(secret, digits, time);
-        let result2 = generate(secret, digits, time);
-        
+
+        // Generate multiple times at the same instant
+        let result1 = generate(secret, &params(digits, time), &CLOCK).unwrap();
+        let result2 = generate(secret, &params(digits, time), &CLOCK).unwrap();
+
         // Results should be the same since time window hasn't changed
         assert_eq!(result1, result2);
     }
 
     #[test]
-    #[should_panic(expected = "Failed to execute oathtool")]
+    fn test_generate_uses_clock() {
+        // "12345678901234567890" in Base32
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(generate(secret, &params(8, 30), &FixedClock(59)).unwrap(), "94287082");
+        assert_eq!(generate(secret, &params(8, 30), &CLOCK).unwrap(), "07081804");
+    }
+
+    #[test]
     fn test_generate_with_invalid_secret() {
-        // This test assumes oathtool will fail with invalid input
         let secret = "INVALID_BASE32_!!!";
         let digits = 6;
         let time = 30;
-        
-        generate(secret, digits, time);
+
+        assert!(generate(secret, &params(digits, time), &CLOCK).is_err());
+    }
+
+    #[test]
+    fn test_generate_with_empty_secret() {
+        assert!(generate("", &params(6, 30), &CLOCK).is_err());
+    }
+
+    #[test]
+    fn test_generate_with_zero_period() {
+        assert!(generate("JBSWY3DPEHPK3PXP", &params(6, 0), &CLOCK).is_err());
     }
 
     #[test]
     fn test_generate_parameter_formatting() {
-        // We can't easily test the actual command execution, but we can test
-        // that our function doesn't panic with various parameters
         let secret = "JBSWY3DPEHPK3PXP";
-        
+
         // Test different digit counts
         for digits in [4, 6, 8] {
-            let result = generate(secret, digits, 30);
+            let result = generate(secret, &params(digits, 30), &CLOCK).unwrap();
             assert_eq!(result.len(), digits as usize);
         }
     }
@@ -88,10 +274,172 @@ mod tests {
         let secret = "MFRGG43TKFRGC5DMMFRGK3TTMVTWK3TTNJQWC3LUHNVWS33OHMVQY2LUHNVWS33OOQQGQ43TMVTWK3DSMFRGK";
         let digits = 6;
         let time = 30;
-        
-        let result = generate(secret, digits, time);
-        
+
+        let result = generate(secret, &params(digits, time), &CLOCK).unwrap();
+
         assert_eq!(result.len(), 6);
         assert!(result.chars().all(|c| c.is_ascii_digit()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_generate_at_rfc6238_time() {
+        // "12345678901234567890" in Base32
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(generate_at(secret, &params(8, 30), 1111111109).unwrap(), "07081804");
+    }
+
+    #[test]
+    fn test_generate_window() {
+        // "12345678901234567890" in Base32
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let codes = generate_window(secret, &params(6, 30), 95, 1).unwrap();
+
+        assert_eq!(codes, vec![
+            (-1, 60, "359152".to_string()),
+            (0, 90, "969429".to_string()),
+            (1, 120, "338314".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_generate_window_stops_at_t0() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let codes = generate_window(secret, &params(6, 30), 10, 2).unwrap();
+
+        let offsets: Vec<i64> = codes.iter().map(|(offset, _, _)| *offset).collect();
+        assert_eq!(offsets, vec![0, 1, 2]);
+        assert_eq!(codes[0].2, "755224");
+    }
+
+    #[test]
+    fn test_verify_totp_current_step() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let found = verify_totp(secret, &params(6, 30), 95, "969429", 0).unwrap();
+        assert_eq!(found, Some((0, 3)));
+    }
+
+    #[test]
+    fn test_verify_totp_with_skew() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let params = params(6, 30);
+
+        assert_eq!(verify_totp(secret, &params, 95, "359152", 1).unwrap(), Some((-1, 2)));
+        assert_eq!(verify_totp(secret, &params, 95, "338314", 1).unwrap(), Some((1, 4)));
+        assert_eq!(verify_totp(secret, &params, 95, "254676", 1).unwrap(), None);
+        assert_eq!(verify_totp(secret, &params, 95, "254676", 2).unwrap(), Some((2, 5)));
+    }
+
+    #[test]
+    fn test_verify_totp_rejects_wrong_length() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(verify_totp(secret, &params(6, 30), 95, "96942", 0).unwrap(), None);
+        assert_eq!(verify_totp(secret, &params(6, 30), 95, "9694290", 0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_verify_hotp() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let params = OtpParams::default();
+
+        assert_eq!(verify_hotp(secret, &params, 3, "254676", 2).unwrap(), Some(5));
+        assert_eq!(verify_hotp(secret, &params, 6, "254676", 10).unwrap(), None);
+    }
+
+    #[test]
+    fn test_seconds_remaining() {
+        let params = params(6, 30);
+        assert_eq!(seconds_remaining(&params, 0), 30);
+        assert_eq!(seconds_remaining(&params, 29), 1);
+        assert_eq!(seconds_remaining(&params, 30), 30);
+        assert_eq!(seconds_remaining(&params, 1111111109), 1);
+
+        let shifted = OtpParams { t0: 10, ..params };
+        assert_eq!(seconds_remaining(&shifted, 35), 5);
+    }
+
+    #[test]
+    fn test_hotp_rfc4226_vectors() {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314",
+            "254676", "287922", "162583", "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(RFC_SHA1_KEY, counter as u64, 6, Algorithm::Sha1).unwrap(), *code);
+        }
+    }
+
+    #[test]
+    fn test_hotp_invalid_digits() {
+        assert!(hotp(RFC_SHA1_KEY, 0, 0, Algorithm::Sha1).is_err());
+        assert!(hotp(RFC_SHA1_KEY, 0, 11, Algorithm::Sha1).is_err());
+    }
+
+    #[test]
+    fn test_generate_hotp() {
+        // "12345678901234567890" in Base32
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(generate_hotp(secret, 0, &OtpParams::default()).unwrap(), "755224");
+        assert_eq!(generate_hotp(secret, 9, &OtpParams::default()).unwrap(), "520489");
+    }
+
+    #[test]
+    fn test_generate_hotp_with_algorithm() {
+        // "12345678901234567890123456789012" in Base32
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+        let params = OtpParams { digits: 8, algorithm: Algorithm::Sha256, ..OtpParams::default() };
+        // RFC 6238 SHA256 vector at T=59, counter 1
+        assert_eq!(generate_hotp(secret, 1, &params).unwrap(), "46119246");
+    }
+
+    #[test]
+    fn test_algorithm_serialization() {
+        assert_eq!(serde_json::to_string(&Algorithm::Sha256).unwrap(), "\"SHA256\"");
+        let algorithm: Algorithm = serde_json::from_str("\"SHA512\"").unwrap();
+        assert_eq!(algorithm, Algorithm::Sha512);
+    }
+
+    #[test]
+    fn test_resync_finds_consecutive_codes() {
+        // Codes for counters 5 and 6, searched from counter 1
+        let found = resync(RFC_SHA1_KEY, 1, 10, "254676", "287922", 6, Algorithm::Sha1).unwrap();
+        assert_eq!(found, Some(6));
+    }
+
+    #[test]
+    fn test_resync_outside_look_ahead() {
+        let found = resync(RFC_SHA1_KEY, 0, 3, "254676", "287922", 6, Algorithm::Sha1).unwrap();
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn test_resync_requires_consecutive_codes() {
+        // Codes for counters 5 and 7
+        let found = resync(RFC_SHA1_KEY, 0, 10, "254676", "162583", 6, Algorithm::Sha1).unwrap();
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        let vectors: [(u64, &str, &str, &str); 6] = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        for (time, sha1, sha256, sha512) in vectors {
+            assert_eq!(totp(RFC_SHA1_KEY, time, 30, 0, 8, Algorithm::Sha1).unwrap(), sha1);
+            assert_eq!(totp(RFC_SHA256_KEY, time, 30, 0, 8, Algorithm::Sha256).unwrap(), sha256);
+            assert_eq!(totp(RFC_SHA512_KEY, time, 30, 0, 8, Algorithm::Sha512).unwrap(), sha512);
+        }
+    }
+
+    #[test]
+    fn test_totp_with_t0() {
+        // Shifting T0 and the timestamp by the same amount keeps the counter
+        let shifted = totp(RFC_SHA1_KEY, 59 + 300, 30, 300, 8, Algorithm::Sha1).unwrap();
+        assert_eq!(shifted, "94287082");
+        assert!(totp(RFC_SHA1_KEY, 10, 30, 300, 8, Algorithm::Sha1).is_err());
+    }
+}
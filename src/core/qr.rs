@@ -0,0 +1,167 @@
+use anyhow::{Context, Error, Result};
+use image::{GrayImage, ImageFormat, Luma};
+use log::warn;
+use qrcode::{render::{svg, unicode::Dense1x2}, QrCode};
+use std::{io::Cursor, path::Path};
+
+/// Pixels per module in PNG output, large enough for phone cameras.
+const PNG_MODULE_SIZE: u32 = 8;
+
+/// Texts of all QR codes found in an image file.
+pub fn read_image(path: &Path) -> Result<Vec<String>> {
+    let image = image::open(path).with_context(
+        || format!("Failed to read image: {}", path.display())
+    )?;
+    let contents = decode(&image.to_luma8());
+    if contents.is_empty() {
+        return Err(Error::msg(format!("No QR code found in {}", path.display())));
+    }
+    Ok(contents)
+}
+
+/// Decodes every readable QR code in the image, in the order they are
+/// detected. Codes that are found but cannot be decoded are reported and
+/// left out.
+pub fn decode(image: &GrayImage) -> Vec<String> {
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| image.get_pixel(x as u32, y as u32).0[0],
+    );
+    prepared.detect_grids()
+        .iter()
+        .filter_map(|grid| match grid.decode() {
+            Ok((_, content)) => Some(content),
+            Err(e) => {
+                warn!("Skipping unreadable QR code: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn encode(text: &str) -> Result<QrCode> {
+    QrCode::new(text).map_err(|e| Error::msg(format!("Failed to encode QR code: {}", e)))
+}
+
+/// QR code drawn with Unicode half blocks, two modules per character cell.
+/// Dark modules are drawn with the foreground color unless `invert` is set,
+/// which suits terminals with a dark background.
+pub fn to_terminal(text: &str, invert: bool) -> Result<String> {
+    let code = encode(text)?;
+    let mut renderer = code.render::<Dense1x2>();
+    if invert {
+        renderer.dark_color(Dense1x2::Light).light_color(Dense1x2::Dark);
+    }
+    Ok(renderer.build())
+}
+
+/// QR code as a PNG image.
+pub fn to_png(text: &str) -> Result<Vec<u8>> {
+    let image = encode(text)?
+        .render::<Luma<u8>>()
+        .module_dimensions(PNG_MODULE_SIZE, PNG_MODULE_SIZE)
+        .build();
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// QR code as an SVG document.
+pub fn to_svg(text: &str) -> Result<String> {
+    Ok(encode(text)?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qrcode::Color;
+
+    /// Draws `text` as a QR code at `offset`, 4 pixels per module.
+    fn draw(image: &mut GrayImage, text: &str, offset: u32) {
+        let code = QrCode::new(text).unwrap();
+        let width = code.width() as u32;
+        for (i, color) in code.to_colors().into_iter().enumerate() {
+            if color == Color::Dark {
+                let (x, y) = (i as u32 % width, i as u32 / width);
+                for dy in 0..4 {
+                    for dx in 0..4 {
+                        image.put_pixel(offset + 16 + x * 4 + dx, 16 + y * 4 + dy, Luma([0]));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_single_code() {
+        let uri = "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Example";
+        let mut image = GrayImage::from_pixel(200, 200, Luma([255]));
+        draw(&mut image, uri, 0);
+
+        assert_eq!(decode(&image), vec![uri.to_string()]);
+    }
+
+    #[test]
+    fn test_decode_several_codes() {
+        let mut image = GrayImage::from_pixel(400, 200, Luma([255]));
+        draw(&mut image, "otpauth://totp/first?secret=JBSWY3DPEHPK3PXP", 0);
+        draw(&mut image, "otpauth://totp/second?secret=JBSWY3DPEHPK3PXP", 200);
+
+        let mut contents = decode(&image);
+        contents.sort();
+        assert_eq!(contents, vec![
+            "otpauth://totp/first?secret=JBSWY3DPEHPK3PXP".to_string(),
+            "otpauth://totp/second?secret=JBSWY3DPEHPK3PXP".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_decode_without_code() {
+        let image = GrayImage::from_pixel(100, 100, Luma([255]));
+        assert!(decode(&image).is_empty());
+    }
+
+    #[test]
+    fn test_read_image_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("code.png");
+        let mut image = GrayImage::from_pixel(200, 200, Luma([255]));
+        draw(&mut image, "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP", 0);
+        image.save(&path).unwrap();
+
+        assert_eq!(read_image(&path).unwrap(), vec!["otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP".to_string()]);
+        assert!(read_image(&dir.path().join("missing.png")).is_err());
+    }
+
+    #[test]
+    fn test_png_roundtrip() {
+        let uri = "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Example";
+        let image = image::load_from_memory(&to_png(uri).unwrap()).unwrap();
+        assert_eq!(decode(&image.to_luma8()), vec![uri.to_string()]);
+    }
+
+    #[test]
+    fn test_to_terminal() {
+        let text = "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP";
+        let width = encode(text).unwrap().width() + 8;
+        let rendered = to_terminal(text, false).unwrap();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), width.div_ceil(2));
+        assert!(lines.iter().all(|line| line.chars().count() == width));
+        assert!(lines[0].chars().all(|c| c == ' '));
+
+        let inverted = to_terminal(text, true).unwrap();
+        assert!(inverted.lines().next().unwrap().chars().all(|c| c == '\u{2588}'));
+    }
+
+    #[test]
+    fn test_to_svg() {
+        let svg = to_svg("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.contains("<svg"));
+    }
+}
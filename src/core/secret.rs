@@ -0,0 +1,129 @@
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+
+use crate::core::base32;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SecretFormat {
+    /// RFC 4648 Base32, as shown by most websites
+    #[default]
+    Base32,
+    /// Hexadecimal bytes, as used by some hardware tokens
+    Hex,
+    /// The secret text itself, used as raw bytes
+    Raw,
+}
+
+/// Converts a user supplied secret to canonical Base32: uppercase, without
+/// separators or padding.
+pub fn normalize(input: &str, format: SecretFormat) -> Result<String> {
+    let bytes = match format {
+        SecretFormat::Base32 => return normalize_base32(input),
+        SecretFormat::Hex => decode_hex(input)?,
+        SecretFormat::Raw => input.as_bytes().to_vec(),
+    };
+    if bytes.is_empty() {
+        return Err(Error::msg("Secret is empty"));
+    }
+    Ok(base32::encode(&bytes))
+}
+
+fn normalize_base32(input: &str) -> Result<String> {
+    let cleaned: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .trim_end_matches('=')
+        .to_ascii_uppercase();
+
+    if cleaned.is_empty() {
+        return Err(Error::msg("Secret is empty"));
+    }
+    if let Some(c) = cleaned.chars().find(|c| !matches!(c, 'A'..='Z' | '2'..='7')) {
+        return Err(Error::msg(format!(
+            "Invalid Base32 secret: '{}' is not allowed (use A-Z and 2-7, or --secret-format)", c
+        )));
+    }
+    // Only these lengths modulo 8 come from whole bytes
+    if matches!(cleaned.len() % 8, 1 | 3 | 6) {
+        return Err(Error::msg(format!(
+            "Invalid Base32 secret: {} characters cannot encode whole bytes, check for a missing character",
+            cleaned.len()
+        )));
+    }
+    Ok(cleaned)
+}
+
+fn decode_hex(input: &str) -> Result<Vec<u8>> {
+    let cleaned: String = input.chars().filter(|c| !c.is_whitespace() && *c != ':' && *c != '-').collect();
+    let cleaned = cleaned.strip_prefix("0x").unwrap_or(&cleaned);
+    if let Some(c) = cleaned.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(Error::msg(format!("Invalid hex secret: '{}' is not a hex digit", c)));
+    }
+    if !cleaned.len().is_multiple_of(2) {
+        return Err(Error::msg("Invalid hex secret: odd number of digits"));
+    }
+    Ok((0..cleaned.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cleaned[i..i + 2], 16).unwrap())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_base32_strips_separators() {
+        let secret = normalize("jbsw y3dp-ehpk 3pxp", SecretFormat::Base32).unwrap();
+        assert_eq!(secret, "JBSWY3DPEHPK3PXP");
+    }
+
+    #[test]
+    fn test_normalize_base32_fixes_padding() {
+        assert_eq!(normalize("MZXW6YQ=", SecretFormat::Base32).unwrap(), "MZXW6YQ");
+        assert_eq!(normalize("MZXW6YQ", SecretFormat::Base32).unwrap(), "MZXW6YQ");
+        assert_eq!(normalize("MZXW6YQ===", SecretFormat::Base32).unwrap(), "MZXW6YQ");
+    }
+
+    #[test]
+    fn test_normalize_base32_rejects_invalid_characters() {
+        let error = normalize("JBSWY3DPEHPK3PX1", SecretFormat::Base32).unwrap_err();
+        assert!(error.to_string().contains("'1'"));
+        assert!(normalize("JBSWY3DP_HPK3PXP", SecretFormat::Base32).is_err());
+    }
+
+    #[test]
+    fn test_normalize_base32_rejects_truncated_secret() {
+        assert!(normalize("JBSWY3DPEHPK3PX", SecretFormat::Base32).is_ok());
+        assert!(normalize("JBSWY3DPEHPK3P", SecretFormat::Base32).is_err());
+    }
+
+    #[test]
+    fn test_normalize_empty_secret() {
+        assert!(normalize(" - ", SecretFormat::Base32).is_err());
+        assert!(normalize("", SecretFormat::Hex).is_err());
+        assert!(normalize("", SecretFormat::Raw).is_err());
+    }
+
+    #[test]
+    fn test_normalize_hex() {
+        // RFC 4226 test secret "12345678901234567890"
+        let hex = "3132333435363738393031323334353637383930";
+        assert_eq!(normalize(hex, SecretFormat::Hex).unwrap(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(normalize("0x3132 3334", SecretFormat::Hex).unwrap(), base32::encode(b"1234"));
+    }
+
+    #[test]
+    fn test_normalize_invalid_hex() {
+        assert!(normalize("313", SecretFormat::Hex).is_err());
+        assert!(normalize("31zz", SecretFormat::Hex).is_err());
+        assert!(normalize("3é", SecretFormat::Hex).is_err());
+    }
+
+    #[test]
+    fn test_normalize_raw() {
+        let secret = normalize("12345678901234567890", SecretFormat::Raw).unwrap();
+        assert_eq!(secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+}
@@ -0,0 +1,612 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path, sync::Mutex};
+
+use crate::core::{clock::{Clock, SystemClock}, config::{self, StorageMode}, encryption::{self, KdfParams}, entry::Entry, file_system, names};
+
+pub fn get_secrets_dir() -> Result<String> {
+    let secrets_dir = Path::new(&file_system::get_app_data_dir()?).join("secrets");
+    if !secrets_dir.exists() {
+        file_system::create_dir_all(&secrets_dir)?;
+    }
+    Ok(secrets_dir.to_str().unwrap().to_string())
+}
+
+/// File of an entry. Names are validated and encoded by `names`, so they
+/// can never point outside the secrets directory. Files written before
+/// names were encoded are still used where they exist.
+pub fn secret_path(service_name: &str) -> Result<String> {
+    let secrets_dir = get_secrets_dir()?;
+    let path = Path::new(&secrets_dir).join(names::to_path(service_name)?);
+    if let Some(legacy) = names::legacy_path(service_name).map(|legacy| Path::new(&secrets_dir).join(legacy)) {
+        if !path.exists() && legacy.exists() {
+            return Ok(legacy.to_str().unwrap().to_string());
+        }
+    }
+    Ok(path.to_str().unwrap().to_string())
+}
+
+/// Removes the group directories of a deleted entry file that are left
+/// empty, stopping at the secrets directory.
+fn remove_empty_groups(path: &str, secrets_dir: &str) {
+    let secrets_dir = Path::new(secrets_dir);
+    let mut dir = Path::new(path).parent();
+    while let Some(group) = dir.filter(|group| *group != secrets_dir && group.starts_with(secrets_dir)) {
+        // Fails once a directory still has entries in it
+        if std::fs::remove_dir(group).is_err() {
+            break;
+        }
+        dir = group.parent();
+    }
+}
+
+/// Creates the group directories an entry file goes into.
+fn create_parent_dir(path: &str) -> Result<()> {
+    if let Some(parent) = Path::new(path).parent() {
+        file_system::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+fn vault_path() -> Result<String> {
+    let path = Path::new(&file_system::get_app_data_dir()?).join("vault.bin");
+    Ok(path.to_str().unwrap().to_string())
+}
+
+/// Salt shared by every entry of the vault, created on first use.
+fn vault_salt() -> Result<Vec<u8>> {
+    let path = Path::new(&file_system::get_app_data_dir()?).join("vault.salt");
+    let path = path.to_str().unwrap();
+    if file_system::exists(path)? {
+        let salt = file_system::read_bin(path)?;
+        if salt.len() != encryption::SALT_LEN {
+            return Err(anyhow::anyhow!("Invalid vault salt: {}", path));
+        }
+        return Ok(salt);
+    }
+
+    let salt = encryption::generate_salt();
+    file_system::write_bin(path, &salt)?;
+    Ok(salt)
+}
+
+fn vault_kdf() -> Result<(KdfParams, Vec<u8>)> {
+    Ok((config::read().kdf_params(), vault_salt()?))
+}
+
+fn storage() -> StorageMode {
+    config::read().storage
+}
+
+/// Every entry of a single-file vault, keyed by service name.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+struct Vault {
+    entries: BTreeMap<String, Entry>,
+}
+
+/// Vault plaintext is padded with spaces to a multiple of this size, so the
+/// file size does not give away how many entries it holds.
+const VAULT_BUCKET_SIZE: usize = 4096;
+
+/// Decrypted vault kept for the rest of the invocation, keyed by path and
+/// password digest.
+type VaultCacheEntry = (String, [u8; 32], Vault);
+static VAULT_CACHE: Mutex<Option<VaultCacheEntry>> = Mutex::new(None);
+
+pub fn exists(service_name: &str, password: &str) -> Result<bool> {
+    match storage() {
+        StorageMode::Files => file_system::exists(&secret_path(service_name)?),
+        StorageMode::Vault => Ok(load_vault(&vault_path()?, password)?.entries.contains_key(service_name)),
+    }
+}
+
+/// Names of all stored entries, sorted.
+pub fn list_names(password: &str) -> Result<Vec<String>> {
+    match storage() {
+        StorageMode::Files => list_file_names(),
+        StorageMode::Vault => Ok(load_vault(&vault_path()?, password)?.entries.into_keys().collect()),
+    }
+}
+
+/// Names of the entries kept as one file each, whatever the storage mode.
+pub fn list_file_names() -> Result<Vec<String>> {
+    let mut names: Vec<String> = file_system::list_files_recursive(&get_secrets_dir()?)?
+        .iter()
+        .filter_map(|path| names::from_path(path))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Every entry with its name, sorted by name.
+pub fn load_all(password: &str) -> Result<Vec<(String, Entry)>> {
+    match storage() {
+        StorageMode::Files => list_file_names()?
+            .into_iter()
+            .map(|name| {
+                let entry = read_entry(&secret_path(&name)?, password)?;
+                Ok((name, entry))
+            })
+            .collect(),
+        StorageMode::Vault => Ok(load_vault(&vault_path()?, password)?.entries.into_iter().collect()),
+    }
+}
+
+pub fn load(service_name: &str, password: &str) -> Result<Entry> {
+    match storage() {
+        StorageMode::Files => read_entry(&secret_path(service_name)?, password),
+        StorageMode::Vault => load_vault(&vault_path()?, password)?.entries.remove(service_name).ok_or_else(
+            || anyhow::anyhow!("Service not found: {}", service_name)
+        ),
+    }
+}
+
+pub fn save(service_name: &str, entry: &Entry, password: &str) -> Result<()> {
+    names::validate(service_name)?;
+    let (kdf, salt) = vault_kdf()?;
+    match storage() {
+        StorageMode::Files => {
+            let path = secret_path(service_name)?;
+            create_parent_dir(&path)?;
+            write_entry(&path, entry, password, &kdf, &salt)
+        }
+        StorageMode::Vault => update_vault(&vault_path()?, password, &kdf, &salt, |vault| {
+            vault.entries.insert(service_name.to_string(), entry.clone());
+            Ok(())
+        }),
+    }
+}
+
+pub fn remove(service_name: &str, password: &str) -> Result<()> {
+    match storage() {
+        StorageMode::Files => {
+            let path = secret_path(service_name)?;
+            file_system::delete_file(&path)?;
+            remove_empty_groups(&path, &get_secrets_dir()?);
+            Ok(())
+        }
+        StorageMode::Vault => {
+            let (kdf, salt) = vault_kdf()?;
+            update_vault(&vault_path()?, password, &kdf, &salt, |vault| {
+                vault.entries.remove(service_name).map(|_| ()).ok_or_else(
+                    || anyhow::anyhow!("Service not found: {}", service_name)
+                )
+            })
+        }
+    }
+}
+
+pub fn rename(old_name: &str, new_name: &str, password: &str) -> Result<()> {
+    names::validate(new_name)?;
+    match storage() {
+        StorageMode::Files => rename_entry_file(&secret_path(old_name)?, &secret_path(new_name)?),
+        StorageMode::Vault => {
+            let (kdf, salt) = vault_kdf()?;
+            update_vault(&vault_path()?, password, &kdf, &salt, |vault| {
+                if vault.entries.contains_key(new_name) {
+                    return Err(anyhow::anyhow!("Entry already exists: {}", new_name));
+                }
+                let entry = vault.entries.remove(old_name).ok_or_else(
+                    || anyhow::anyhow!("Service not found: {}", old_name)
+                )?;
+                vault.entries.insert(new_name.to_string(), entry);
+                Ok(())
+            })
+        }
+    }
+}
+
+/// Whether the password decrypts the vault, or the first entry file when
+/// there is no vault. `None` when nothing is stored to check it against.
+pub fn check_password(password: &str) -> Result<Option<bool>> {
+    let path = vault_path()?;
+    if file_system::exists(&path)? {
+        return Ok(Some(read_vault(&path, password).is_ok()));
+    }
+    match list_file_names()?.first() {
+        Some(name) => Ok(Some(read_entry(&secret_path(name)?, password).is_ok())),
+        None => Ok(None),
+    }
+}
+
+fn rename_entry_file(old_path: &str, new_path: &str) -> Result<()> {
+    create_parent_dir(new_path)?;
+    let _old_lock = file_system::lock(old_path)?;
+    let _new_lock = file_system::lock(new_path)?;
+
+    if !file_system::exists(old_path)? {
+        return Err(anyhow::anyhow!("Entry not found: {}", old_path));
+    }
+    if file_system::exists(new_path)? {
+        return Err(anyhow::anyhow!("Entry already exists: {}", new_path));
+    }
+    std::fs::rename(old_path, new_path).with_context(
+        || format!("Failed to rename {} to {}", old_path, new_path)
+    )
+}
+
+/// Read-modify-write of an entry while holding its lock, so concurrent
+/// invocations never hand out the same HOTP counter twice. Expired secret
+/// history is dropped on the way.
+pub fn update<T>(service_name: &str, password: &str, apply: impl FnOnce(&mut Entry) -> Result<T>) -> Result<T> {
+    let (kdf, salt) = vault_kdf()?;
+    let (now, history_days) = (SystemClock.now()?, config::read().secret_history_days);
+    let apply = |entry: &mut Entry| {
+        entry.prune_secret_history(now, history_days);
+        apply(entry)
+    };
+    match storage() {
+        StorageMode::Files => update_entry(&secret_path(service_name)?, password, &kdf, &salt, apply),
+        StorageMode::Vault => update_vault(&vault_path()?, password, &kdf, &salt, |vault| {
+            let entry = vault.entries.get_mut(service_name).ok_or_else(
+                || anyhow::anyhow!("Service not found: {}", service_name)
+            )?;
+            apply(entry)
+        }),
+    }
+}
+
+fn update_entry<T>(path: &str, password: &str, kdf: &KdfParams, salt: &[u8], apply: impl FnOnce(&mut Entry) -> Result<T>) -> Result<T> {
+    let _lock = file_system::lock(path)?;
+
+    let mut entry = read_entry(path, password)?;
+    let result = apply(&mut entry)?;
+    write_entry(path, &entry, password, kdf, salt)?;
+    Ok(result)
+}
+
+/// Rewrites an entry file in the current file format once it decrypts.
+/// Returns the format version it was migrated from, or `None` if already current.
+pub fn migrate(service_name: &str, password: &str) -> Result<Option<u8>> {
+    let (kdf, salt) = vault_kdf()?;
+    migrate_entry(&secret_path(service_name)?, password, &kdf, &salt)
+}
+
+fn migrate_entry(path: &str, password: &str, kdf: &KdfParams, salt: &[u8]) -> Result<Option<u8>> {
+    let _lock = file_system::lock(path)?;
+
+    let encrypted = encryption::EncryptedSecret::from_bytes(&file_system::read_bin(path)?)?;
+    if !encrypted.needs_migration() {
+        return Ok(None);
+    }
+    let entry = Entry::from_plain(&encryption::decrypt(&encrypted, password)?);
+    write_entry(path, &entry, password, kdf, salt)?;
+    Ok(Some(encrypted.version()))
+}
+
+/// Moves an entry file into the single-file vault and deletes the file.
+pub fn move_into_vault(service_name: &str, password: &str) -> Result<()> {
+    let (kdf, salt) = vault_kdf()?;
+    move_entry_into_vault(service_name, &secret_path(service_name)?, &vault_path()?, password, &kdf, &salt)
+}
+
+fn move_entry_into_vault(service_name: &str, entry_path: &str, vault_path: &str, password: &str, kdf: &KdfParams, salt: &[u8]) -> Result<()> {
+    let entry = read_entry(entry_path, password)?;
+    update_vault(vault_path, password, kdf, salt, |vault| {
+        if vault.entries.contains_key(service_name) {
+            return Err(anyhow::anyhow!("Entry already exists in the vault: {}", service_name));
+        }
+        vault.entries.insert(service_name.to_string(), entry);
+        Ok(())
+    })?;
+    file_system::delete_file(entry_path)
+}
+
+fn read_entry(path: &str, password: &str) -> Result<Entry> {
+    let secret_bin = file_system::read_bin(path).with_context(
+        || format!("Failed to read entry: {}", path)
+    )?;
+    let encrypted = encryption::EncryptedSecret::from_bytes(&secret_bin).with_context(
+        || format!("Failed to parse entry: {}", path)
+    )?;
+    let plain = encryption::decrypt(&encrypted, password)?;
+    Ok(Entry::from_plain(&plain))
+}
+
+fn write_entry(path: &str, entry: &Entry, password: &str, kdf: &KdfParams, salt: &[u8]) -> Result<()> {
+    let encrypted = encryption::encrypt(&entry.to_plain()?, password, kdf, salt)?;
+    let encrypted_bin = encrypted.to_bytes()?;
+    file_system::write_bin(path, &encrypted_bin)
+}
+
+/// Returns the vault, decrypting it only on first use in this process.
+fn load_vault(path: &str, password: &str) -> Result<Vault> {
+    let digest = encryption::password_digest(password);
+    let mut cache = VAULT_CACHE.lock().unwrap();
+    if let Some((cached_path, cached_digest, vault)) = cache.as_ref() {
+        if cached_path == path && *cached_digest == digest {
+            return Ok(vault.clone());
+        }
+    }
+
+    let vault = read_vault(path, password)?;
+    *cache = Some((path.to_string(), digest, vault.clone()));
+    Ok(vault)
+}
+
+/// A missing vault file is an empty vault.
+fn read_vault(path: &str, password: &str) -> Result<Vault> {
+    if !file_system::exists(path)? {
+        return Ok(Vault::default());
+    }
+    let vault_bin = file_system::read_bin(path).with_context(
+        || format!("Failed to read vault: {}", path)
+    )?;
+    let encrypted = encryption::EncryptedSecret::from_bytes(&vault_bin).with_context(
+        || format!("Failed to parse vault: {}", path)
+    )?;
+    let plain = encryption::decrypt(&encrypted, password)?;
+    serde_json::from_str(&plain).with_context(|| format!("Failed to parse vault: {}", path))
+}
+
+fn write_vault(path: &str, vault: &Vault, password: &str, kdf: &KdfParams, salt: &[u8]) -> Result<()> {
+    let mut plain = serde_json::to_string(vault)?;
+    // Trailing whitespace is valid JSON, so padded vaults read back as is
+    let padded_len = plain.len().div_ceil(VAULT_BUCKET_SIZE) * VAULT_BUCKET_SIZE;
+    plain.extend(std::iter::repeat_n(' ', padded_len - plain.len()));
+    let encrypted = encryption::encrypt(&plain, password, kdf, salt)?;
+    file_system::write_bin(path, &encrypted.to_bytes()?)
+}
+
+/// Read-modify-write of the whole vault under its lock. The file is read
+/// again rather than taken from the cache so concurrent writers are not lost.
+fn update_vault<T>(path: &str, password: &str, kdf: &KdfParams, salt: &[u8], apply: impl FnOnce(&mut Vault) -> Result<T>) -> Result<T> {
+    let _lock = file_system::lock(path)?;
+
+    let mut vault = read_vault(path, password)?;
+    let result = apply(&mut vault)?;
+    write_vault(path, &vault, password, kdf, salt)?;
+    *VAULT_CACHE.lock().unwrap() = Some((path.to_string(), encryption::password_digest(password), vault));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::entry::OtpKind;
+    use tempfile::TempDir;
+
+    const PASSWORD: &str = "this_is_a_very_long_password_32chars";
+    const KDF: KdfParams = KdfParams { memory_kib: 8, iterations: 1, parallelism: 1 };
+    const SALT: &[u8] = b"0123456789abcdef";
+
+    #[test]
+    fn test_write_read_entry() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("entry.bin");
+        let path = path.to_str().unwrap();
+
+        let entry = Entry::hotp("JBSWY3DPEHPK3PXP", 7);
+        write_entry(path, &entry, PASSWORD, &KDF, SALT).unwrap();
+
+        assert_eq!(read_entry(path, PASSWORD).unwrap(), entry);
+        assert!(read_entry(path, "this_is_a_different_password_32chars").is_err());
+    }
+
+    #[test]
+    fn test_update_entry_persists_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("counter.bin");
+        let path = path.to_str().unwrap();
+
+        write_entry(path, &Entry::hotp("JBSWY3DPEHPK3PXP", 0), PASSWORD, &KDF, SALT).unwrap();
+        for _ in 0..3 {
+            update_entry(path, PASSWORD, &KDF, SALT, |entry| {
+                if let OtpKind::Hotp { counter } = &mut entry.kind {
+                    *counter += 1;
+                }
+                Ok(())
+            }).unwrap();
+        }
+
+        assert_eq!(read_entry(path, PASSWORD).unwrap(), Entry::hotp("JBSWY3DPEHPK3PXP", 3));
+        assert!(!temp_dir.path().join("counter.bin.lock").exists());
+    }
+
+    #[test]
+    fn test_update_entry_failure_keeps_entry() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("failing.bin");
+        let path = path.to_str().unwrap();
+
+        write_entry(path, &Entry::hotp("JBSWY3DPEHPK3PXP", 5), PASSWORD, &KDF, SALT).unwrap();
+        let result: Result<()> = update_entry(path, PASSWORD, &KDF, SALT, |entry| {
+            entry.kind = OtpKind::Hotp { counter: 6 };
+            Err(anyhow::anyhow!("failed"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(read_entry(path, PASSWORD).unwrap(), Entry::hotp("JBSWY3DPEHPK3PXP", 5));
+    }
+
+    #[test]
+    fn test_read_legacy_entry() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("legacy.bin");
+        let path = path.to_str().unwrap();
+
+        let encrypted = encryption::encrypt("JBSWY3DPEHPK3PXP", PASSWORD, &KDF, SALT).unwrap();
+        file_system::write_bin(path, &encrypted.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(read_entry(path, PASSWORD).unwrap(), Entry::totp("JBSWY3DPEHPK3PXP"));
+    }
+
+    #[test]
+    fn test_read_entry_written_before_key_derivation() {
+        use aes_gcm::{aead::{Aead, KeyInit}, Aes256Gcm, Nonce};
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("unsalted.bin");
+        let path = path.to_str().unwrap();
+
+        // Old files used the first 32 password bytes as the AES key
+        let cipher = Aes256Gcm::new_from_slice(&PASSWORD.as_bytes()[..32]).unwrap();
+        let nonce = [7u8; 12];
+        let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), b"JBSWY3DPEHPK3PXP".as_ref()).unwrap();
+        file_system::write_bin(path, &bincode::serialize(&(nonce.to_vec(), ciphertext)).unwrap()).unwrap();
+
+        assert_eq!(read_entry(path, PASSWORD).unwrap(), Entry::totp("JBSWY3DPEHPK3PXP"));
+        assert!(read_entry(path, "this_is_a_different_password_32chars").is_err());
+    }
+
+    #[test]
+    fn test_migrate_entry_rewrites_legacy_file() {
+        use aes_gcm::{aead::{Aead, KeyInit}, Aes256Gcm, Nonce};
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("migrate.bin");
+        let path = path.to_str().unwrap();
+
+        let cipher = Aes256Gcm::new_from_slice(&PASSWORD.as_bytes()[..32]).unwrap();
+        let nonce = [7u8; 12];
+        let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), b"JBSWY3DPEHPK3PXP".as_ref()).unwrap();
+        file_system::write_bin(path, &bincode::serialize(&(nonce.to_vec(), ciphertext)).unwrap()).unwrap();
+
+        // A wrong password leaves the file untouched
+        assert!(migrate_entry(path, "this_is_a_different_password_32chars", &KDF, SALT).is_err());
+        assert!(encryption::EncryptedSecret::from_bytes(&file_system::read_bin(path).unwrap()).unwrap().needs_migration());
+
+        assert_eq!(migrate_entry(path, PASSWORD, &KDF, SALT).unwrap(), Some(0));
+        let migrated = encryption::EncryptedSecret::from_bytes(&file_system::read_bin(path).unwrap()).unwrap();
+        assert!(!migrated.needs_migration());
+        assert_eq!(read_entry(path, PASSWORD).unwrap(), Entry::totp("JBSWY3DPEHPK3PXP"));
+
+        // Running again is a no-op
+        assert_eq!(migrate_entry(path, PASSWORD, &KDF, SALT).unwrap(), None);
+    }
+
+    #[test]
+    fn test_update_vault_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("vault.bin");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(read_vault(path, PASSWORD).unwrap(), Vault::default());
+        update_vault(path, PASSWORD, &KDF, SALT, |vault| {
+            vault.entries.insert("github".to_string(), Entry::totp("JBSWY3DPEHPK3PXP"));
+            vault.entries.insert("bank".to_string(), Entry::hotp("GEZDGNBVGY3TQOJQ", 3));
+            Ok(())
+        }).unwrap();
+
+        let vault = read_vault(path, PASSWORD).unwrap();
+        assert_eq!(vault.entries.keys().collect::<Vec<_>>(), ["bank", "github"]);
+        assert_eq!(vault.entries["bank"], Entry::hotp("GEZDGNBVGY3TQOJQ", 3));
+        assert!(read_vault(path, "this_is_a_different_password_32chars").is_err());
+        assert!(!temp_dir.path().join("vault.bin.lock").exists());
+    }
+
+    #[test]
+    fn test_vault_file_hides_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("vault.bin");
+        let path = path.to_str().unwrap();
+
+        update_vault(path, PASSWORD, &KDF, SALT, |vault| {
+            vault.entries.insert("my-secret-bank".to_string(), Entry::totp("JBSWY3DPEHPK3PXP"));
+            Ok(())
+        }).unwrap();
+
+        let contents = file_system::read_bin(path).unwrap();
+        assert!(!contents.windows(b"my-secret-bank".len()).any(|w| w == b"my-secret-bank"));
+    }
+
+    #[test]
+    fn test_vault_file_size_hides_entry_count() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("vault.bin");
+        let path = path.to_str().unwrap();
+
+        update_vault(path, PASSWORD, &KDF, SALT, |vault| {
+            vault.entries.insert("github".to_string(), Entry::totp("JBSWY3DPEHPK3PXP"));
+            Ok(())
+        }).unwrap();
+        let one_entry = file_system::read_bin(path).unwrap().len();
+        update_vault(path, PASSWORD, &KDF, SALT, |vault| {
+            for i in 0..5 {
+                vault.entries.insert(format!("service-{}", i), Entry::totp("GEZDGNBVGY3TQOJQ"));
+            }
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(file_system::read_bin(path).unwrap().len(), one_entry);
+        assert_eq!(read_vault(path, PASSWORD).unwrap().entries.len(), 6);
+    }
+
+    #[test]
+    fn test_update_vault_failure_keeps_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("vault.bin");
+        let path = path.to_str().unwrap();
+
+        update_vault(path, PASSWORD, &KDF, SALT, |vault| {
+            vault.entries.insert("github".to_string(), Entry::totp("JBSWY3DPEHPK3PXP"));
+            Ok(())
+        }).unwrap();
+        let result: Result<()> = update_vault(path, PASSWORD, &KDF, SALT, |vault| {
+            vault.entries.clear();
+            Err(anyhow::anyhow!("failed"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(read_vault(path, PASSWORD).unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn test_move_entry_into_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let entry_path = temp_dir.path().join("github.bin");
+        let entry_path = entry_path.to_str().unwrap();
+        let vault_path = temp_dir.path().join("vault.bin");
+        let vault_path = vault_path.to_str().unwrap();
+
+        write_entry(entry_path, &Entry::totp("JBSWY3DPEHPK3PXP"), PASSWORD, &KDF, SALT).unwrap();
+        move_entry_into_vault("github", entry_path, vault_path, PASSWORD, &KDF, SALT).unwrap();
+
+        assert!(!file_system::exists(entry_path).unwrap());
+        assert_eq!(read_vault(vault_path, PASSWORD).unwrap().entries["github"], Entry::totp("JBSWY3DPEHPK3PXP"));
+
+        // A name already in the vault is not overwritten and the file is kept
+        write_entry(entry_path, &Entry::totp("GEZDGNBVGY3TQOJQ"), PASSWORD, &KDF, SALT).unwrap();
+        assert!(move_entry_into_vault("github", entry_path, vault_path, PASSWORD, &KDF, SALT).is_err());
+        assert!(file_system::exists(entry_path).unwrap());
+        assert_eq!(read_vault(vault_path, PASSWORD).unwrap().entries["github"], Entry::totp("JBSWY3DPEHPK3PXP"));
+    }
+
+    #[test]
+    fn test_rename_entry_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let old_path = temp_dir.path().join("old.bin");
+        let old_path = old_path.to_str().unwrap();
+        let new_path = temp_dir.path().join("new.bin");
+        let new_path = new_path.to_str().unwrap();
+
+        write_entry(old_path, &Entry::totp("JBSWY3DPEHPK3PXP"), PASSWORD, &KDF, SALT).unwrap();
+        rename_entry_file(old_path, new_path).unwrap();
+
+        assert!(!file_system::exists(old_path).unwrap());
+        assert_eq!(read_entry(new_path, PASSWORD).unwrap(), Entry::totp("JBSWY3DPEHPK3PXP"));
+        assert!(rename_entry_file(old_path, new_path).is_err());
+
+        // Never overwrites an existing entry
+        write_entry(old_path, &Entry::totp("GEZDGNBVGY3TQOJQ"), PASSWORD, &KDF, SALT).unwrap();
+        assert!(rename_entry_file(old_path, new_path).is_err());
+        assert_eq!(read_entry(new_path, PASSWORD).unwrap(), Entry::totp("JBSWY3DPEHPK3PXP"));
+        assert!(!temp_dir.path().join("old.bin.lock").exists());
+        assert!(!temp_dir.path().join("new.bin.lock").exists());
+    }
+
+    #[test]
+    fn test_remove_empty_groups() {
+        let temp_dir = TempDir::new().unwrap();
+        let secrets_dir = temp_dir.path().to_str().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("work/aws/old")).unwrap();
+        std::fs::write(temp_dir.path().join("work/github.bin"), b"").unwrap();
+
+        let path = temp_dir.path().join("work/aws/old/prod.bin");
+        remove_empty_groups(path.to_str().unwrap(), secrets_dir);
+
+        assert!(!temp_dir.path().join("work/aws").exists());
+        assert!(temp_dir.path().join("work/github.bin").exists());
+        assert!(temp_dir.path().exists());
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{Context, Error, Result};
+use chrono::DateTime;
+use std::{net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970).
+const NTP_UNIX_OFFSET: f64 = 2_208_988_800.0;
+const NTP_PACKET_SIZE: usize = 48;
+/// Leap indicator 0, version 4, mode 3 (client).
+const NTP_CLIENT_HEADER: u8 = 0x23;
+const NTP_MODE_SERVER: u8 = 4;
+
+fn local_time() -> Result<f64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64())
+}
+
+fn ntp_timestamp(bytes: &[u8]) -> f64 {
+    let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64;
+    let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as f64;
+    seconds + fraction / 4_294_967_296.0 - NTP_UNIX_OFFSET
+}
+
+/// Offset in seconds to add to the local clock to match an SNTP server
+/// (RFC 4330), e.g. `pool.ntp.org:123`. Each address of the server is
+/// tried in turn, over IPv4 or IPv6 as the address requires.
+pub fn sntp_offset(server: &str, timeout: Duration) -> Result<f64> {
+    let addresses = server.to_socket_addrs().with_context(
+        || format!("Failed to resolve SNTP server: {}", server)
+    )?;
+    let mut last_error = None;
+    for address in addresses {
+        match sntp_query(address, timeout) {
+            Ok(offset) => return Ok(offset),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| Error::msg("No address found")).context(
+        format!("No answer from SNTP server: {}", server)
+    ))
+}
+
+fn sntp_query(address: SocketAddr, timeout: Duration) -> Result<f64> {
+    let local: SocketAddr = match address {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(address).with_context(
+        || format!("Failed to reach {}", address)
+    )?;
+
+    let mut request = [0u8; NTP_PACKET_SIZE];
+    request[0] = NTP_CLIENT_HEADER;
+    let sent = local_time()?;
+    socket.send(&request)?;
+
+    let mut response = [0u8; NTP_PACKET_SIZE];
+    let size = socket.recv(&mut response).with_context(
+        || format!("No answer from {}", address)
+    )?;
+    let received = local_time()?;
+    if size < NTP_PACKET_SIZE || response[0] & 0x07 != NTP_MODE_SERVER {
+        return Err(Error::msg(format!("Invalid SNTP answer from {}", address)));
+    }
+
+    let server_received = ntp_timestamp(&response[32..40]);
+    let server_sent = ntp_timestamp(&response[40..48]);
+    Ok(((server_received - sent) + (server_sent - received)) / 2.0)
+}
+
+/// Offset in seconds to add to the local clock to match the `Date` header
+/// returned by `url`. Only accurate to about one second.
+pub fn http_offset(url: &str, timeout: Duration) -> Result<f64> {
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    let sent = local_time()?;
+    let response = match agent.head(url).call() {
+        Ok(response) => response,
+        // Error pages still carry a Date header
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(e) => return Err(Error::msg(format!("Failed to reach {}: {}", url, e))),
+    };
+    let received = local_time()?;
+
+    let date = response.header("Date").with_context(
+        || format!("No Date header in the answer from {}", url)
+    )?;
+    let server_time = DateTime::parse_from_rfc2822(date).with_context(
+        || format!("Invalid Date header from {}: {}", url, date)
+    )?;
+    Ok(server_time.timestamp() as f64 - (sent + received) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::{Read, Write}, net::TcpListener, thread};
+
+    const SKEW: f64 = 100.0;
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn ntp_bytes(unix_time: f64) -> [u8; 8] {
+        let ntp_time = unix_time + NTP_UNIX_OFFSET;
+        let seconds = ntp_time.trunc() as u32;
+        let fraction = (ntp_time.fract() * 4_294_967_296.0) as u32;
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+        bytes[4..].copy_from_slice(&fraction.to_be_bytes());
+        bytes
+    }
+
+    /// Local SNTP server on `host` whose clock runs `SKEW` seconds ahead.
+    fn spawn_sntp_server(host: &str) -> String {
+        let socket = UdpSocket::bind((host, 0)).unwrap();
+        let address = socket.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let mut request = [0u8; NTP_PACKET_SIZE];
+            let (_, client) = socket.recv_from(&mut request).unwrap();
+            let now = ntp_bytes(local_time().unwrap() + SKEW);
+
+            let mut response = [0u8; NTP_PACKET_SIZE];
+            response[0] = 0x24;
+            response[32..40].copy_from_slice(&now);
+            response[40..48].copy_from_slice(&now);
+            socket.send_to(&response, client).unwrap();
+        });
+        address
+    }
+
+    /// Local HTTP server answering with a `Date` header `SKEW` seconds ahead.
+    fn spawn_http_server(status: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}/", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).unwrap();
+
+            let date = DateTime::from_timestamp((local_time().unwrap() + SKEW) as i64, 0).unwrap();
+            let response = format!(
+                "HTTP/1.1 {}\r\nDate: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status, date.format("%a, %d %b %Y %H:%M:%S GMT")
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        address
+    }
+
+    #[test]
+    fn test_ntp_timestamp() {
+        assert_eq!(ntp_timestamp(&ntp_bytes(1111111109.5)), 1111111109.5);
+    }
+
+    #[test]
+    fn test_sntp_offset() {
+        let server = spawn_sntp_server("127.0.0.1");
+        let offset = sntp_offset(&server, TIMEOUT).unwrap();
+        assert!((offset - SKEW).abs() < 1.0, "offset was {}", offset);
+    }
+
+    #[test]
+    fn test_sntp_offset_ipv6() {
+        let server = spawn_sntp_server("::1");
+        let offset = sntp_offset(&server, TIMEOUT).unwrap();
+        assert!((offset - SKEW).abs() < 1.0, "offset was {}", offset);
+    }
+
+    #[test]
+    fn test_sntp_offset_without_server() {
+        // Bind and drop a socket to get a port nobody listens on
+        let address = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        assert!(sntp_offset(&address, Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    fn test_http_offset() {
+        let url = spawn_http_server("200 OK");
+        let offset = http_offset(&url, TIMEOUT).unwrap();
+        assert!((offset - SKEW).abs() < 2.0, "offset was {}", offset);
+    }
+
+    #[test]
+    fn test_http_offset_on_error_status() {
+        let url = spawn_http_server("404 Not Found");
+        let offset = http_offset(&url, TIMEOUT).unwrap();
+        assert!((offset - SKEW).abs() < 2.0, "offset was {}", offset);
+    }
+}
+
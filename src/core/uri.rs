@@ -0,0 +1,258 @@
+use anyhow::{Error, Result};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::core::{entry::{Entry, OtpKind}, names, oathtool::{Algorithm, OtpParams}, secret::{self, SecretFormat}};
+
+const SCHEME: &str = "otpauth://";
+
+/// Characters escaped in otpauth labels and query values (RFC 3986 unreserved are kept).
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, COMPONENT).to_string()
+}
+
+fn decode(value: &str) -> Result<String> {
+    Ok(percent_decode_str(value).decode_utf8()?.into_owned())
+}
+
+pub fn algorithm_name(algorithm: Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::Sha1 => "SHA1",
+        Algorithm::Sha256 => "SHA256",
+        Algorithm::Sha512 => "SHA512",
+    }
+}
+
+/// URI of a stored entry, labelled with its account or else its name.
+pub fn from_entry(service_name: &str, entry: &Entry, params: &OtpParams) -> String {
+    build(entry.account.as_deref().unwrap_or(service_name), entry.issuer.as_deref(), entry, params)
+}
+
+fn parse_algorithm(name: &str) -> Result<Algorithm> {
+    match name.to_ascii_uppercase().replace('-', "").as_str() {
+        "SHA1" => Ok(Algorithm::Sha1),
+        "SHA256" => Ok(Algorithm::Sha256),
+        "SHA512" => Ok(Algorithm::Sha512),
+        _ => Err(Error::msg(format!("Unsupported algorithm: {}", name))),
+    }
+}
+
+/// Parses a Key Uri Format `otpauth://` URI into a new entry. Parameters
+/// missing from the URI get the defaults of the format, not the ones from
+/// the configuration.
+pub fn parse(uri: &str) -> Result<Entry> {
+    let uri = uri.trim();
+    let rest = uri.get(..SCHEME.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+        .map(|_| &uri[SCHEME.len()..])
+        .ok_or_else(|| Error::msg("Not an otpauth:// URI"))?;
+    let (otp_type, rest) = rest.split_once('/').ok_or_else(|| Error::msg("Missing label in URI"))?;
+    let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+    let mut secret = None;
+    let mut issuer = None;
+    let mut algorithm = None;
+    let mut digits = None;
+    let mut period = None;
+    let mut counter = None;
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        // Some generators form-encode spaces as `+`
+        let value = decode(&value.replace('+', " "))?;
+        match key.to_ascii_lowercase().as_str() {
+            "secret" => secret = Some(secret::normalize(&value, SecretFormat::Base32)?),
+            "issuer" => issuer = Some(value),
+            "algorithm" => algorithm = Some(parse_algorithm(&value)?),
+            "digits" => digits = Some(value.parse::<u32>().ok().filter(|d| (1..=10).contains(d))
+                .ok_or_else(|| Error::msg(format!("Invalid digits: {}", value)))?),
+            "period" => period = Some(value.parse::<u64>().ok().filter(|p| *p > 0)
+                .ok_or_else(|| Error::msg(format!("Invalid period: {}", value)))?),
+            "counter" => counter = Some(value.parse::<u64>()
+                .map_err(|_| Error::msg(format!("Invalid counter: {}", value)))?),
+            // e.g. `image`, shown by some apps
+            _ => {}
+        }
+    }
+    let secret = secret.ok_or_else(|| Error::msg("Missing secret in URI"))?;
+
+    // The issuer prefix is split off before decoding, so an account may
+    // contain an escaped `:`. An escaped separator is only recognized when
+    // it matches the issuer parameter.
+    let (label_issuer, account) = match label.split_once(':') {
+        Some((label_issuer, account)) => (Some(decode(label_issuer)?), decode(account)?),
+        None => {
+            let label = decode(label)?;
+            match label.split_once(':').filter(|(prefix, _)| Some(*prefix) == issuer.as_deref()) {
+                Some((label_issuer, account)) => (Some(label_issuer.to_string()), account.to_string()),
+                None => (None, label),
+            }
+        }
+    };
+    let (label_issuer, account) = (label_issuer.map(|issuer| issuer.trim().to_string()), account.trim().to_string());
+
+    let mut entry = match otp_type.to_ascii_lowercase().as_str() {
+        "totp" => {
+            let mut entry = Entry::totp(&secret);
+            entry.period = Some(period.unwrap_or(OtpParams::default().period));
+            entry
+        }
+        "hotp" => Entry::hotp(&secret, counter.unwrap_or(0)),
+        _ => return Err(Error::msg(format!("Unsupported OTP type: {}", otp_type))),
+    };
+    entry.digits = Some(digits.unwrap_or(OtpParams::default().digits));
+    entry.algorithm = algorithm;
+    entry.issuer = issuer.or(label_issuer).filter(|issuer| !issuer.is_empty());
+    entry.account = Some(account).filter(|account| !account.is_empty());
+    Ok(entry)
+}
+
+/// Name for an imported entry: `issuer/account`, or whichever of them is
+/// known, made valid for `names`.
+pub fn suggest_name(entry: &Entry) -> Result<String> {
+    let clean = |value: &str| value.replace(names::SEPARATOR, "-").trim().to_string();
+    let name = match (entry.issuer.as_deref(), entry.account.as_deref()) {
+        (Some(issuer), Some(account)) => format!("{}{}{}", clean(issuer), names::SEPARATOR, clean(account)),
+        (Some(value), None) | (None, Some(value)) => clean(value),
+        (None, None) => return Err(Error::msg("URI has no label or issuer to name the entry after")),
+    };
+    names::validate(&name)?;
+    Ok(name)
+}
+
+/// Builds a Key Uri Format `otpauth://` URI, understood by phone authenticators.
+pub fn build(account: &str, issuer: Option<&str>, entry: &Entry, params: &OtpParams) -> String {
+    let (otp_type, moving_factor) = match entry.kind {
+        OtpKind::Totp => ("totp", format!("period={}", params.period)),
+        OtpKind::Hotp { counter } => ("hotp", format!("counter={}", counter)),
+    };
+    let label = match issuer {
+        Some(issuer) => format!("{}:{}", encode(issuer), encode(account)),
+        None => encode(account),
+    };
+
+    let mut uri = format!("otpauth://{}/{}?secret={}", otp_type, label, encode(&entry.secret));
+    if let Some(issuer) = issuer {
+        uri.push_str(&format!("&issuer={}", encode(issuer)));
+    }
+    uri.push_str(&format!(
+        "&algorithm={}&digits={}&{}",
+        algorithm_name(params.algorithm), params.digits, moving_factor
+    ));
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_totp_uri() {
+        let entry = Entry::totp("JBSWY3DPEHPK3PXP");
+        let uri = build("alice@example.com", Some("Example"), &entry, &OtpParams::default());
+
+        assert_eq!(
+            uri,
+            "otpauth://totp/Example:alice%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=Example&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_build_hotp_uri_without_issuer() {
+        let entry = Entry::hotp("JBSWY3DPEHPK3PXP", 12);
+        let params = OtpParams { digits: 8, algorithm: Algorithm::Sha256, ..OtpParams::default() };
+
+        assert_eq!(
+            build("vpn", None, &entry, &params),
+            "otpauth://hotp/vpn?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256&digits=8&counter=12"
+        );
+    }
+
+    #[test]
+    fn test_build_escapes_label() {
+        let entry = Entry::totp("JBSWY3DPEHPK3PXP");
+        let uri = build("my account", Some("ACME Co"), &entry, &OtpParams::default());
+
+        assert!(uri.starts_with("otpauth://totp/ACME%20Co:my%20account?"));
+        assert!(uri.contains("&issuer=ACME%20Co&"));
+    }
+
+    #[test]
+    fn test_parse_totp_uri() {
+        let entry = parse("otpauth://totp/ACME%20Co:john.doe@email.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60").unwrap();
+
+        assert_eq!(entry.secret, "HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ");
+        assert_eq!(entry.kind, OtpKind::Totp);
+        assert_eq!(entry.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(entry.account.as_deref(), Some("john.doe@email.com"));
+        assert_eq!(entry.algorithm, Some(Algorithm::Sha256));
+        assert_eq!(entry.digits, Some(8));
+        assert_eq!(entry.period, Some(60));
+    }
+
+    #[test]
+    fn test_parse_uses_format_defaults() {
+        let entry = parse("otpauth://hotp/vpn?secret=jbswy3dpehpk3pxp&counter=7").unwrap();
+
+        assert_eq!(entry.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(entry.kind, OtpKind::Hotp { counter: 7 });
+        assert_eq!(entry.issuer, None);
+        assert_eq!(entry.account.as_deref(), Some("vpn"));
+        assert_eq!(entry.algorithm, None);
+        assert_eq!(entry.digits, Some(6));
+        assert_eq!(entry.period, None);
+    }
+
+    #[test]
+    fn test_parse_issuer_from_label_or_parameter() {
+        let from_label = parse("otpauth://totp/GitHub:%20alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(from_label.issuer.as_deref(), Some("GitHub"));
+        assert_eq!(from_label.account.as_deref(), Some("alice"));
+
+        let from_parameter = parse("OTPAUTH://TOTP/alice?secret=JBSWY3DPEHPK3PXP&issuer=Big+Corp").unwrap();
+        assert_eq!(from_parameter.issuer.as_deref(), Some("Big Corp"));
+
+        let escaped = parse("otpauth://totp/GitHub%3Aalice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub").unwrap();
+        assert_eq!(escaped.issuer.as_deref(), Some("GitHub"));
+        assert_eq!(escaped.account.as_deref(), Some("alice"));
+
+        let colon_in_account = parse("otpauth://totp/urn%3Aalice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(colon_in_account.issuer, None);
+        assert_eq!(colon_in_account.account.as_deref(), Some("urn:alice"));
+    }
+
+    #[test]
+    fn test_parse_invalid_uris() {
+        assert!(parse("https://example.com/?secret=JBSWY3DPEHPK3PXP").is_err());
+        assert!(parse("otpauth://totp/alice").is_err());
+        assert!(parse("otpauth://totp/alice?secret=not-base32!").is_err());
+        assert!(parse("otpauth://motp/alice?secret=JBSWY3DPEHPK3PXP").is_err());
+        assert!(parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=MD5").is_err());
+        assert!(parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits=0").is_err());
+        assert!(parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&period=0").is_err());
+    }
+
+    #[test]
+    fn test_build_parse_roundtrip() {
+        let mut entry = Entry::hotp("JBSWY3DPEHPK3PXP", 3);
+        entry.issuer = Some("ACME Co".to_string());
+        entry.account = Some("a:b@example.com".to_string());
+        entry.digits = Some(8);
+        entry.algorithm = Some(Algorithm::Sha512);
+        let params = OtpParams { digits: 8, algorithm: Algorithm::Sha512, ..OtpParams::default() };
+
+        let uri = build("a:b@example.com", Some("ACME Co"), &entry, &params);
+        assert_eq!(parse(&uri).unwrap(), entry);
+    }
+
+    #[test]
+    fn test_suggest_name() {
+        let mut entry = Entry::totp("JBSWY3DPEHPK3PXP");
+        assert!(suggest_name(&entry).is_err());
+
+        entry.account = Some("alice@example.com".to_string());
+        assert_eq!(suggest_name(&entry).unwrap(), "alice@example.com");
+        entry.issuer = Some("Example/EU".to_string());
+        assert_eq!(suggest_name(&entry).unwrap(), "Example-EU/alice@example.com");
+    }
+}
@@ -9,6 +9,11 @@ mod commands;
 fn main() -> Result<()> {
     env_logger::init();
     let cmd = commands::Cli::parse();
+    if let Err(e) = core::file_system::set_location(cmd.location()).and_then(|_| migrate_legacy_dir()) {
+        log::error!("{}", e);
+        std::process::exit(1);
+    }
+    warn_on_loose_permissions();
 
     // Run git-cliff
     let mut exit_code = 0;
@@ -19,3 +24,36 @@ fn main() -> Result<()> {
 
     std::process::exit(exit_code);
 }
+
+fn migrate_legacy_dir() -> Result<()> {
+    if let Some((legacy, data)) = core::file_system::migrate_legacy_dir()? {
+        eprintln!(
+            "Moved {} to {} and its config to {}",
+            legacy.display(), data.display(), core::file_system::get_config_dir()?
+        );
+    }
+    Ok(())
+}
+
+/// Secrets are encrypted, but names, metadata sizes and the salt should
+/// still not be readable by other local users.
+fn warn_on_loose_permissions() {
+    let loose = core::file_system::app_dirs().and_then(|dirs| {
+        let mut loose = core::file_system::loose_permissions(&core::file_system::get_app_data_dir()?)?;
+        if dirs.config != dirs.data {
+            loose.extend(core::file_system::loose_permissions(&core::file_system::get_config_dir()?)?);
+        }
+        Ok(loose)
+    });
+    match loose {
+        Ok(paths) => for (path, mode) in paths {
+            let expected = if path.is_dir() { core::file_system::DIR_MODE } else { core::file_system::FILE_MODE };
+            // Printed even without RUST_LOG, the user has to act on it
+            eprintln!(
+                "Warning: {} is accessible by other users (mode {:o}), fix it with: chmod {:o} {}",
+                path.display(), mode, expected, path.display()
+            );
+        },
+        Err(e) => eprintln!("Warning: failed to check permissions: {}", e),
+    }
+}